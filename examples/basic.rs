@@ -14,11 +14,11 @@ struct User {
 // Run via: `POSTGREST_JWT=your_auth_jwt cargo run --example basic`
 #[tokio::main]
 async fn main() {
-	let auth_key = format!("Bearer {}", std::env::var("POSTGREST_JWT").unwrap_or(String::from("")).to_string());
+	let auth_key = format!("Bearer {}", std::env::var("POSTGREST_JWT").unwrap_or_default());
 	let mut headers = HeaderMap::new();
 	headers.insert("Authorization", auth_key.parse().unwrap());
 	headers.insert("Content-Type", "application/json".parse().unwrap());
-	let db = PostgrestClient::new(POSTGREST_URL.to_string(), Some(headers));
+	let db = PostgrestClient::new(POSTGREST_URL.to_string(), Some(headers)).unwrap();
 
 	let query = db.from("users").find_many::<User>().exec().await;
 
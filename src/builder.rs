@@ -1,17 +1,21 @@
-use std::str::FromStr;
-
 use crate::{
 	filter::{FilterType, PostgrestFilter},
-	handler::{PostgrestError, PostgrestHandler},
+	handler::PostgrestError,
+	http::HttpClient,
+	prefer::{Missing, Preferences, Resolution},
+	state::{Filtered, Insert, Read, Single, SingleUnfiltered, Unfiltered, Upsert},
+	transport::Transport,
+	value::FilterValue,
 };
 use reqwest::{
-	header::{HeaderMap, HeaderName},
+	header::{HeaderMap, HeaderValue, ACCEPT},
 	Method,
 };
 use serde::{de::DeserializeOwned, Serialize};
+use std::sync::Arc;
 use url::Url;
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Count {
 	Exact,
 	Planned,
@@ -29,285 +33,353 @@ impl std::fmt::Display for Count {
 	}
 }
 
+/// How much of a mutation PostgREST sends back (`Prefer: return=...`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Return {
+	/// Nothing but the status and `Content-Range`
+	Minimal,
+	/// Also a `Location` header pointing at the created row
+	HeadersOnly,
+	/// The affected rows in the response body
+	Representation,
+}
+
+impl std::fmt::Display for Return {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let lowercase_str = match self {
+			Return::Minimal => "minimal",
+			Return::HeadersOnly => "headers-only",
+			Return::Representation => "representation",
+		};
+		write!(f, "{}", lowercase_str)
+	}
+}
+
+/// Media type asking PostgREST for a single JSON object instead of an array, it responds with a 406 unless exactly
+/// one row matched
+///
+/// https://postgrest.org/en/stable/references/api/resource_representation.html#singular-or-plural
+pub const SINGLE_OBJECT: &str = "application/vnd.pgrst.object+json";
+
 #[derive(Debug, PartialEq, Eq)]
 pub enum PostgrestQuery {
 	FindUnique,
+	FindFirst,
 	FindMany,
 	Create,
 	CreateMany,
 	Update,
 	UpdateMany,
+	Upsert,
 	Delete,
 	DeleteMany,
 	Call,
+	/// `HEAD` request resolving to the total number of rows in `Content-Range`, fails when PostgREST didn't count them
+	Count,
+	/// `HEAD` request resolving to the number of rows in the range `Content-Range` returned, see `exists`
+	Exists,
 }
 
 pub struct PostgrestQueryBuilder {
 	pub url: Url,
 	pub headers: Option<HeaderMap>,
+	/// Schema to query instead of the default exposed one, see `schema`
+	pub schema: Option<String>,
+	pub transport: Arc<dyn Transport>,
 }
 
 impl PostgrestQueryBuilder {
-	pub fn new(url: String, headers: Option<HeaderMap>) -> Self {
+	pub fn new(url: Url, headers: Option<HeaderMap>) -> Self {
 		PostgrestQueryBuilder {
-			url: Url::parse(&url).expect("Failed to parse PostgrestQueryBuilder.url"),
+			url,
 			headers,
+			schema: None,
+			transport: Arc::new(HttpClient::default()),
 		}
 	}
 
+	/// Query the relation in another exposed schema, overriding the one set on `PostgrestClient`
+	///
+	/// # Example
+	/// ```ignore
+	/// db.from("invoices").schema("billing").find_many::<Invoice>().exec().await;
+	/// ```
+	pub fn schema(mut self, schema: &str) -> Self {
+		self.schema = Some(schema.to_string());
+		self
+	}
+
+	fn filter<T, B, S>(self, method: Method, body: Option<B>, query_type: PostgrestQuery) -> PostgrestFilter<T, B, S>
+	where
+		T: Serialize + DeserializeOwned,
+		B: Serialize + DeserializeOwned,
+	{
+		let mut filter = PostgrestFilter::new(self.url, method, self.headers, body, query_type);
+		filter.schema = self.schema;
+		filter.transport = self.transport;
+		filter
+	}
+
 	/// Perform a SELECT query on the table/view (similar to `find_many` except this requires filters)
 	///
+	/// Resolves to the row itself, asking PostgREST for a single object (`application/vnd.pgrst.object+json`). Fails
+	/// with `PostgrestError::RowNotFound` when nothing matches and `PostgrestError::MultipleRows` when more than one
+	/// row does.
+	///
 	/// # Example
-	pub fn find_unique<T, U>(mut self, filter_column: &str, filter_type: FilterType, filter_value: U) -> PostgrestExecBuilder<T>
+	/// ```ignore
+	/// let user = db.from("users").find_unique::<User, _>("id", FilterType::Eq, 1).exec().await?;
+	/// ```
+	pub fn find_unique<T, U>(mut self, filter_column: &str, filter_type: FilterType, filter_value: U) -> PostgrestFilter<T, T, Single>
 	where
 		T: Serialize + DeserializeOwned,
-		U: Serialize + DeserializeOwned + ToString,
+		U: Into<FilterValue>,
 	{
-		let filter_str = format!("{}.{}", filter_type.to_string(), filter_value.to_string());
+		self.headers
+			.get_or_insert_with(HeaderMap::new)
+			.insert(ACCEPT, HeaderValue::from_static(SINGLE_OBJECT));
 
-		self.url.query_pairs_mut().append_pair(filter_column, filter_str.as_str());
+		self.filter::<T, T, Single>(Method::GET, None, PostgrestQuery::FindUnique)
+			.filter(filter_column, filter_value, filter_type)
+	}
 
-		PostgrestExecBuilder::new(self.url, self.headers, Method::GET, PostgrestQuery::FindUnique)
+	/// Perform a SELECT query on the table/view resolving to its first row, or `None` when no row matches
+	///
+	/// Only one row is fetched (`limit=1`), pair it with `order` to choose which one.
+	///
+	/// # Example
+	/// ```ignore
+	/// let newest: Option<Post> = db.from("posts").find_first::<Post>().eq("author_id", 1).order(Order::desc("created_at")).exec().await?;
+	/// ```
+	pub fn find_first<T>(self) -> PostgrestFilter<Option<T>, T, Read>
+	where
+		T: Serialize + DeserializeOwned,
+	{
+		self.filter(Method::GET, None, PostgrestQuery::FindFirst).limit(1)
 	}
 
 	/// Perform a SELECT query on the table/view
 	///
 	/// # Example
-	pub fn find_many<T: Serialize + DeserializeOwned>(self) -> PostgrestFilter<Vec<T>, T>
+	pub fn find_many<T>(self) -> PostgrestFilter<Vec<T>, T, Read>
 	where
 		T: Serialize + DeserializeOwned,
 	{
-		PostgrestFilter::new(self.url, Method::GET, self.headers, None, PostgrestQuery::FindMany)
+		self.filter(Method::GET, None, PostgrestQuery::FindMany)
 	}
 
-	/// Perform an INSERT into the table or view.
+	/// Perform an INSERT into the table or view, resolving to the number of inserted rows.
+	///
+	/// Use `.returning::<T>()` to get the inserted rows back instead.
 	///
 	/// # Example
-	pub fn create<T>(mut self, values: T, default_to_null: Option<bool>, count: Option<Count>) -> PostgrestFilter<i32, T>
+	pub fn create<T>(self, values: T, default_to_null: Option<bool>, count: Option<Count>) -> PostgrestFilter<u64, T, Insert>
 	where
 		T: Serialize + DeserializeOwned,
 	{
-		let mut postgrest_pref_headers: Vec<&str> = Vec::new();
-		let mut new_headers = self.headers.clone().unwrap_or_else(HeaderMap::new);
-
-		if let Some(headers) = &self.headers {
-			if let Some(prefer) = headers.get("Prefers") {
-				postgrest_pref_headers.push(prefer.to_str().unwrap());
-			}
-		}
-
-		if let Some(val) = default_to_null {
-			if !val {
-				postgrest_pref_headers.push("missing=default");
-			}
-		}
-
 		// https://postgrest.org/en/stable/references/api/pagination_count.html?highlight=count
-		if let Some(val) = count {
-			match val {
-				Count::Exact => postgrest_pref_headers.push("count=exact"),
-				Count::Planned => postgrest_pref_headers.push("count=planned"),
-				Count::Estimated => postgrest_pref_headers.push("count=estimated"),
-			}
-		}
-
-		new_headers.insert(HeaderName::from_str("Prefer").unwrap(), postgrest_pref_headers.join(",").parse().unwrap());
-
-		self.headers = Some(new_headers);
+		let prefer = Preferences {
+			count,
+			missing: missing(default_to_null),
+			..Default::default()
+		};
 
-		PostgrestFilter::new(self.url, Method::POST, self.headers, Some(values), PostgrestQuery::Create)
+		count_exact(self.filter(Method::POST, Some(values), PostgrestQuery::Create).prefer(prefer))
 	}
 
-	/// Perform an INSERT of many items into the table or view.
+	/// Perform an INSERT of many items into the table or view, resolving to the number of inserted rows.
+	///
+	/// Use `.returning::<T>()` to get the inserted rows back instead.
 	///
 	/// # Example
-	pub fn create_many<T>(mut self, values: Vec<T>, default_to_null: Option<bool>, count: Option<Count>) -> PostgrestFilter<i32, Vec<T>>
+	pub fn create_many<T>(self, values: Vec<T>, default_to_null: Option<bool>, count: Option<Count>) -> PostgrestFilter<u64, Vec<T>, Insert>
 	where
 		T: Serialize + DeserializeOwned,
 	{
-		let mut postgrest_pref_headers: Vec<&str> = Vec::new();
-		let mut new_headers = self.headers.clone().unwrap_or_else(HeaderMap::new);
-
-		if let Some(headers) = &self.headers {
-			if let Some(prefer) = headers.get("Prefers") {
-				postgrest_pref_headers.push(prefer.to_str().unwrap());
-			}
-		}
-
-		if let Some(val) = default_to_null {
-			if !val {
-				postgrest_pref_headers.push("missing=default");
-			}
-		}
-
-		// https://postgrest.org/en/stable/references/api/pagination_count.html?highlight=count
-		if let Some(val) = count {
-			match val {
-				Count::Exact => postgrest_pref_headers.push("count=exact"),
-				Count::Planned => postgrest_pref_headers.push("count=planned"),
-				Count::Estimated => postgrest_pref_headers.push("count=estimated"),
-			}
-		}
-
-		new_headers.insert(HeaderName::from_str("Prefer").unwrap(), postgrest_pref_headers.join(",").parse().unwrap());
-
-		self.headers = Some(new_headers);
+		let prefer = Preferences {
+			count,
+			missing: missing(default_to_null),
+			..Default::default()
+		};
 
-		PostgrestFilter::new(self.url, Method::POST, self.headers, Some(values), PostgrestQuery::CreateMany)
+		count_exact(self.filter(Method::POST, Some(values), PostgrestQuery::CreateMany).prefer(prefer))
 	}
 
-	/// Perform an UPDATE on the table or view.
+	/// Perform an UPDATE on the table or view, resolving to the number of updated rows.
+	///
+	/// Use `.returning::<T>()` to get the updated rows back instead. Fails with `PostgrestError::UnfilteredMutation`
+	/// unless it's filtered or `.all_rows()` is used.
 	///
 	/// # Example
-	pub fn update<T>(mut self, values: T, count: Option<Count>) -> PostgrestFilter<T, T>
+	/// ```ignore
+	/// let renamed: Vec<User> = db.from("users").update(patch, None).eq("id", 1).returning::<User>().select("id,name").exec().await?;
+	/// ```
+	pub fn update<T>(self, values: T, count: Option<Count>) -> PostgrestFilter<u64, T, Unfiltered>
 	where
 		T: Serialize + DeserializeOwned,
 	{
-		let mut postgrest_pref_headers: Vec<&str> = Vec::new();
-		let mut new_headers = self.headers.clone().unwrap_or_else(HeaderMap::new);
+		let prefer = Preferences { count, ..Default::default() };
 
-		if let Some(headers) = &self.headers {
-			if let Some(prefer) = headers.get("Prefers") {
-				postgrest_pref_headers.push(prefer.to_str().unwrap());
-			}
-		}
-
-		// https://postgrest.org/en/stable/references/api/pagination_count.html?highlight=count
-		if let Some(val) = count {
-			match val {
-				Count::Exact => postgrest_pref_headers.push("count=exact"),
-				Count::Planned => postgrest_pref_headers.push("count=planned"),
-				Count::Estimated => postgrest_pref_headers.push("count=estimated"),
-			}
-		}
-
-		new_headers.insert(HeaderName::from_str("Prefer").unwrap(), postgrest_pref_headers.join(",").parse().unwrap());
-		self.headers = Some(new_headers);
+		count_exact(self.filter(Method::PATCH, Some(values), PostgrestQuery::Update).prefer(prefer))
+	}
 
-		PostgrestFilter::new(self.url, Method::PATCH, self.headers, Some(values), PostgrestQuery::Update)
+	/// Perform an UPDATE that must affect exactly one row, resolving to the updated row
+	///
+	/// Fails with `PostgrestError::RowNotFound` or `PostgrestError::MultipleRows` otherwise, in which case PostgREST
+	/// rolls the update back.
+	///
+	/// # Example
+	/// ```ignore
+	/// let user: User = db.from("users").update_one(patch).eq("id", 1).exec().await?;
+	/// ```
+	pub fn update_one<T>(self, values: T) -> PostgrestFilter<T, T, SingleUnfiltered>
+	where
+		T: Serialize + DeserializeOwned,
+	{
+		single_object(self.filter(Method::PATCH, Some(values), PostgrestQuery::Update))
 	}
 
-	///  Perform an UPSERT on the table or view.
+	///  Perform an UPSERT on the table or view, resolving to the number of inserted or updated rows.
+	///
+	/// Use `.returning::<T>()` to get the rows back instead.
 	///
 	/// # Example
 	pub fn upsert<T>(
-		mut self,
+		self,
 		values: T,
 		on_conflict: Option<String>,
 		default_to_null: Option<bool>,
 		count: Option<Count>,
 		ignore_duplicates: Option<bool>,
-	) -> PostgrestFilter<T, T>
+	) -> PostgrestFilter<u64, T, Upsert>
 	where
 		T: Serialize + DeserializeOwned,
 	{
-		let ignore_duplicates = if ignore_duplicates.unwrap_or(false) { "ignore" } else { "merge" };
-
-		let resolution = format!("resolution={ignoreDuplicates}-duplicates", ignoreDuplicates = ignore_duplicates);
-
-		let mut postgrest_pref_headers: Vec<&str> = vec![&resolution];
-		let mut new_headers = self.headers.clone().unwrap_or_else(HeaderMap::new);
-
-		if let Some(on_conflict) = on_conflict {
-			self.url.query_pairs_mut().append_pair("on_conflict", &on_conflict);
-		}
-
-		if let Some(headers) = &self.headers {
-			if let Some(prefer) = headers.get("Prefers") {
-				postgrest_pref_headers.push(prefer.to_str().unwrap());
-			}
-		}
-
-		if let Some(val) = default_to_null {
-			if !val {
-				postgrest_pref_headers.push("missing=default");
-			}
-		}
+		let resolution = ignore_duplicates.map(|ignore| match ignore {
+			true => Resolution::IgnoreDuplicates,
+			false => Resolution::MergeDuplicates,
+		});
+		let prefer = Preferences {
+			resolution,
+			missing: missing(default_to_null),
+			count,
+			..Default::default()
+		};
 
-		// https://postgrest.org/en/stable/references/api/pagination_count.html?highlight=count
-		if let Some(val) = count {
-			match val {
-				Count::Exact => postgrest_pref_headers.push("count=exact"),
-				Count::Planned => postgrest_pref_headers.push("count=planned"),
-				Count::Estimated => postgrest_pref_headers.push("count=estimated"),
-			}
+		let mut filter = count_exact(self.filter(Method::POST, Some(values), PostgrestQuery::Upsert).prefer(prefer));
+		filter.prefer.resolution.get_or_insert(Resolution::MergeDuplicates);
+		match on_conflict {
+			Some(on_conflict) => filter.on_conflict(&on_conflict),
+			None => filter,
 		}
+	}
 
-		new_headers.insert(HeaderName::from_str("Prefer").unwrap(), postgrest_pref_headers.join(",").parse().unwrap());
-
-		self.headers = Some(new_headers);
+	/// Perform a DELETE query on the table/view, resolving to the number of deleted rows.
+	///
+	/// Use `.returning::<T>()` to get the deleted rows back instead.
+	///
+	/// Fails with `PostgrestError::UnfilteredMutation` unless it's filtered or `.all_rows()` is used.
+	///
+	/// # Example
+	pub fn delete<T>(self, count: Option<Count>) -> PostgrestFilter<u64, T, Unfiltered>
+	where
+		T: Serialize + DeserializeOwned,
+	{
+		let prefer = Preferences { count, ..Default::default() };
 
-		PostgrestFilter::new(self.url, Method::POST, self.headers, Some(values), PostgrestQuery::Update)
+		count_exact(self.filter(Method::DELETE, None, PostgrestQuery::Delete).prefer(prefer))
 	}
 
-	/// Perform a DELETE query on the table/view.
+	/// Perform a DELETE that must affect exactly one row, resolving to the deleted row
+	///
+	/// Fails with `PostgrestError::RowNotFound` or `PostgrestError::MultipleRows` otherwise, in which case PostgREST
+	/// rolls the delete back.
 	///
-	/// > Note: using `.delete()` should always be paried with filters to target specific row(s)
 	/// # Example
-	pub fn delete<T>(mut self, count: Option<Count>) -> PostgrestFilter<T, T>
+	/// ```ignore
+	/// let user: User = db.from("users").delete_one::<User>().eq("id", 1).exec().await?;
+	/// ```
+	pub fn delete_one<T>(self) -> PostgrestFilter<T, T, SingleUnfiltered>
 	where
 		T: Serialize + DeserializeOwned,
 	{
-		let mut postgrest_pref_headers: Vec<&str> = Vec::new();
-		let mut new_headers = self.headers.clone().unwrap_or_else(HeaderMap::new);
-
-		if let Some(val) = count {
-			match val {
-				Count::Exact => postgrest_pref_headers.push("count=exact"),
-				Count::Planned => postgrest_pref_headers.push("count=planned"),
-				Count::Estimated => postgrest_pref_headers.push("count=estimated"),
-			}
-		}
-
-		if let Some(headers) = &self.headers {
-			if let Some(prefer) = headers.get("Prefers") {
-				postgrest_pref_headers.insert(0, prefer.to_str().unwrap());
-			}
-		}
+		single_object(self.filter(Method::DELETE, None, PostgrestQuery::Delete))
+	}
 
-		new_headers.insert(HeaderName::from_str("Prefer").unwrap(), postgrest_pref_headers.join(",").parse().unwrap());
-		self.headers = Some(new_headers);
+	/// Perform a DELETE of every row whose `column` is one of `values` (`column=in.(...)`), resolving to the number
+	/// of deleted rows.
+	///
+	/// Use `.returning::<T>()` to get the deleted rows back instead.
+	///
+	/// # Example
+	/// ```ignore
+	/// let deleted = db.from("users").delete_many::<User, _>("id", vec![1, 2, 3], None).exec().await?;
+	/// ```
+	pub fn delete_many<T, V>(self, column: &str, values: Vec<V>, count: Option<Count>) -> PostgrestFilter<u64, T, Filtered>
+	where
+		T: Serialize + DeserializeOwned,
+		V: ToString,
+	{
+		let prefer = Preferences { count, ..Default::default() };
 
-		PostgrestFilter::new(self.url, Method::DELETE, self.headers, None, PostgrestQuery::Delete)
+		let filter = self
+			.filter::<u64, T, Unfiltered>(Method::DELETE, None, PostgrestQuery::DeleteMany)
+			.prefer(prefer);
+		count_exact(filter).in_filter(column, FilterValue::list(values))
 	}
+}
 
-	/// TODO: Maybe it makes sense not to have this?
-	pub fn delete_many(self) {}
+/// Select the schema with `Accept-Profile` for reads and `Content-Profile` for writes and RPC calls
+///
+/// https://postgrest.org/en/stable/references/api/schemas.html
+pub(crate) fn profile_headers(headers: Option<HeaderMap>, schema: Option<&str>, method: &Method) -> Result<Option<HeaderMap>, PostgrestError> {
+	let Some(schema) = schema else {
+		return Ok(headers);
+	};
+	let mut headers = headers.unwrap_or_default();
+	let profile = if *method == Method::GET || *method == Method::HEAD {
+		"Accept-Profile"
+	} else {
+		"Content-Profile"
+	};
+	let value = HeaderValue::from_str(schema).map_err(|_| PostgrestError::InvalidHeader {
+		name: profile.to_string(),
+		value: schema.to_string(),
+	})?;
+	headers.insert(profile, value);
+	Ok(Some(headers))
 }
 
-/// Builder that goes streight to `exec` or `exec_blocking` and doesnt have any filter methods
-pub struct PostgrestExecBuilder<T>
+/// Return the single affected row of a mutation as an object (`application/vnd.pgrst.object+json`)
+fn single_object<T>(mut filter: PostgrestFilter<T, T, SingleUnfiltered>) -> PostgrestFilter<T, T, SingleUnfiltered>
 where
 	T: Serialize + DeserializeOwned,
 {
-	pub url: Url,
-	pub headers: Option<HeaderMap>,
-	pub method: Method,
-	pub query_type: PostgrestQuery,
-	pub _marker: std::marker::PhantomData<T>,
+	filter
+		.headers
+		.get_or_insert_with(HeaderMap::new)
+		.insert(ACCEPT, HeaderValue::from_static(SINGLE_OBJECT));
+	filter.representation = true;
+	filter.prefer.returning = Some(Return::Representation);
+	filter
 }
 
-impl<T> PostgrestExecBuilder<T>
+/// Count the affected rows exactly unless the arguments or the client's `Prefer` header already chose how
+///
+/// The affected row count is read back from `Content-Range`, which PostgREST only fills in when counting.
+fn count_exact<T, B, S>(mut filter: PostgrestFilter<T, B, S>) -> PostgrestFilter<T, B, S>
 where
 	T: Serialize + DeserializeOwned,
+	B: Serialize + DeserializeOwned,
 {
-	pub fn new(url: Url, headers: Option<HeaderMap>, method: Method, query_type: PostgrestQuery) -> Self {
-		PostgrestExecBuilder {
-			url,
-			headers,
-			method,
-			query_type,
-			_marker: std::marker::PhantomData,
-		}
-	}
-
-	pub fn exec_blocking(self) -> Result<T, PostgrestError> {
-		let handler: PostgrestHandler<T> = PostgrestHandler::new(self.url, self.headers, self.method, None, self.query_type);
-		handler.exec_blocking()
-	}
+	filter.prefer.count.get_or_insert(Count::Exact);
+	filter
+}
 
-	pub async fn exec(self) -> Result<T, PostgrestError> {
-		let handler: PostgrestHandler<T> = PostgrestHandler::new(self.url, self.headers, self.method, None, self.query_type);
-		handler.exec().await
+/// `default_to_null: Some(false)` fills missing columns with their defaults (`missing=default`)
+fn missing(default_to_null: Option<bool>) -> Option<Missing> {
+	match default_to_null {
+		Some(false) => Some(Missing::Default),
+		_ => None,
 	}
 }
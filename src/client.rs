@@ -1,61 +1,157 @@
 use crate::builder::{Count, PostgrestQuery, PostgrestQueryBuilder};
-use crate::filter::PostgrestFilter;
-use reqwest::{header::{HeaderMap, HeaderName}, Method};
-use url::Url;
+use crate::filter::{FilterType, PostgrestFilter};
+use crate::handler::PostgrestError;
+use crate::http::{HttpClient, PoolConfig};
+use crate::prefer::Preferences;
+use crate::transport::Transport;
+use crate::value::FilterValue;
+use reqwest::{header::HeaderMap, Method};
+use serde::de::value::MapDeserializer;
 use serde::{de::DeserializeOwned, Serialize};
-use std::str::FromStr;
 use std::collections::HashMap;
-use serde::de::value::MapDeserializer;
+use std::sync::Arc;
+use url::Url;
+/// Entry point for building queries, cheap to clone and safe to share between threads and async tasks
+///
+/// # Example
+/// ```ignore
+/// let db = PostgrestClient::new(URL.to_string(), None)?;
+/// let users = db.from("users").find_many::<User>().exec().await;
+/// let posts = db.from("posts").find_many::<Post>().exec().await;
+/// ```
+#[derive(Debug, Clone)]
 pub struct PostgrestClient {
-	pub url: String,
+	pub url: Url,
 	pub headers: Option<HeaderMap>,
+	/// Schema used for every query and RPC call, PostgREST's default exposed schema when `None`
+	pub schema: Option<String>,
+	/// Sends every query built from this client (and its clones), a pooled `HttpClient` by default
+	pub transport: Arc<dyn Transport>,
 }
 
 impl PostgrestClient {
-	pub fn new(url: String, headers: Option<HeaderMap>) -> PostgrestClient {
-		PostgrestClient { url, headers }
+	/// Fails with `PostgrestError::InvalidUrl` unless `url` is an absolute URL that relations can be appended to
+	pub fn new(url: String, headers: Option<HeaderMap>) -> Result<PostgrestClient, PostgrestError> {
+		let url = Url::parse(&url).map_err(|err| PostgrestError::InvalidUrl(format!("{} ({})", err, url)))?;
+		if url.cannot_be_a_base() {
+			return Err(PostgrestError::InvalidUrl(format!("can't append a path to {}", url)));
+		}
+
+		Ok(PostgrestClient {
+			url,
+			headers,
+			schema: None,
+			transport: Arc::new(HttpClient::default()),
+		})
 	}
 
-	pub fn from(self, relation: &str) -> PostgrestQueryBuilder {
-		let url = format!("{}/{}", self.url, relation);
-		PostgrestQueryBuilder::new(url, self.headers.clone())
+	/// Replace the connection pool with one using `pool`, e.g. to cap idle connections for high-throughput workers
+	///
+	/// # Example
+	/// ```ignore
+	/// let db = PostgrestClient::new(URL.to_string(), None)?.pool(PoolConfig {
+	///     max_idle_per_host: Some(32),
+	///     idle_timeout: Some(Duration::from_secs(30)),
+	/// });
+	/// ```
+	pub fn pool(mut self, pool: PoolConfig) -> Self {
+		self.transport = Arc::new(HttpClient::new(pool));
+		self
 	}
 
-	/// TODO: https://postgrest.org/en/stable/references/api/schemas.html
-	fn schema() {}
+	/// Send requests through your own `Transport` instead of reqwest, e.g. another HTTP client or a test double
+	///
+	/// # Example
+	/// ```ignore
+	/// let db = PostgrestClient::new(URL.to_string(), None)?.transport(MyTransport::default());
+	/// ```
+	pub fn transport<T>(mut self, transport: T) -> Self
+	where
+		T: Transport + 'static,
+	{
+		self.transport = Arc::new(transport);
+		self
+	}
+
+	pub fn from(&self, relation: &str) -> PostgrestQueryBuilder {
+		let mut builder = PostgrestQueryBuilder::new(self.url_for(&[relation]), self.headers.clone());
+		builder.schema = self.schema.clone();
+		builder.transport = self.transport.clone();
+		builder
+	}
+
+	/// Returns a client for another exposed schema, sent as `Accept-Profile`/`Content-Profile`
+	///
+	/// https://postgrest.org/en/stable/references/api/schemas.html
+	///
+	/// # Example
+	/// ```ignore
+	/// let billing = db.schema("billing");
+	/// billing.from("invoices").find_many::<Invoice>().exec().await;
+	/// ```
+	pub fn schema(&self, schema: &str) -> PostgrestClient {
+		PostgrestClient {
+			schema: Some(schema.to_string()),
+			..self.clone()
+		}
+	}
 
 	/// Call a function in your database over HTTP (via postgrest)
-	pub async fn call<T>(mut self, function: &str, head: bool, count: Option<Count>, args: HashMap<&str, serde_json::Value>) -> Result<T, crate::handler::PostgrestError>
+	///
+	/// With `head` the arguments are sent as query parameters, which only works for strings, numbers, booleans and
+	/// arrays of them, anything else fails with `PostgrestError::ArgumentEncoding`.
+	pub async fn call<T>(&self, function: &str, head: bool, count: Option<Count>, args: HashMap<&str, serde_json::Value>) -> Result<T, PostgrestError>
 	where
 		T: Serialize + DeserializeOwned,
 	{
-		let url = format!("{}/rpc/{}", self.url, function);
 		let req_method: Method;
 		let req_body: Option<T>;
-		let mut query_url = Url::parse(&url).expect("Failed to parse PostgrestClient.url");
+		let mut query_url = self.url_for(&["rpc", function]);
 
 		if head {
 			req_method = Method::HEAD;
 			for (key, value) in args {
-				query_url.query_pairs_mut().append_pair(key, value.as_str().unwrap());
+				query_url.query_pairs_mut().append_pair(key, &query_argument(key, &value)?);
 			}
 			req_body = None;
 		} else {
 			req_method = Method::POST;
-			req_body = Some(T::deserialize(MapDeserializer::new(args.into_iter())).unwrap());
+			let body = T::deserialize(MapDeserializer::new(args.into_iter())).map_err(|err| PostgrestError::ArgumentEncoding(err.to_string()))?;
+			req_body = Some(body);
 		}
 
-		if let Some(val) = count {
-			let mut new_headers = self.headers.clone().unwrap_or_else(HeaderMap::new);
-			new_headers.insert(HeaderName::from_str("Prefer").unwrap(), val.to_string().parse().unwrap());
-			self.headers = Some(new_headers);
-		}
+		let prefer = Preferences { count, ..Default::default() };
 
-		PostgrestFilter::new(query_url, req_method, self.headers, req_body, PostgrestQuery::Call).exec().await
+		let mut filter: PostgrestFilter<T, T> =
+			PostgrestFilter::new(query_url, req_method, self.headers.clone(), req_body, PostgrestQuery::Call).prefer(prefer);
+		filter.schema = self.schema.clone();
+		filter.transport = self.transport.clone();
+		filter.exec().await
 	}
 
 	/// TODO
-	pub async fn call_blocking() {
+	pub async fn call_blocking() {}
+
+	/// `url` with `segments` appended to its path, `new` made sure it can have one
+	fn url_for(&self, segments: &[&str]) -> Url {
+		let mut url = self.url.clone();
+		if let Ok(mut path) = url.path_segments_mut() {
+			path.pop_if_empty().extend(segments);
+		}
+		url
+	}
+}
 
+/// Render an RPC argument as a query parameter, arrays use PostgreSQL's `{a,b}` literal
+fn query_argument(key: &str, value: &serde_json::Value) -> Result<String, PostgrestError> {
+	match value {
+		serde_json::Value::String(value) => Ok(value.clone()),
+		serde_json::Value::Number(value) => Ok(value.to_string()),
+		serde_json::Value::Bool(value) => Ok(value.to_string()),
+		serde_json::Value::Array(_) => Ok(FilterValue::from_json(value).render(&FilterType::Cs, false)),
+		_ => Err(PostgrestError::ArgumentEncoding(format!(
+			"argument `{}` can't be sent as a query parameter: {}",
+			key, value
+		))),
 	}
 }
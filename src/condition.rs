@@ -0,0 +1,141 @@
+use crate::{
+	filter::{FilterType, Quantifier},
+	value::FilterValue,
+};
+
+/// A composable filter expression, rendered into PostgREST's logical operators
+///
+/// https://postgrest.org/en/stable/references/api/tables_views.html#logical-operators
+///
+/// # Example
+/// ```ignore
+/// // or=(age.lt.18,and(age.gte.65,not.is_retired.is.true))
+/// let condition = Condition::or(vec![
+///     Condition::filter("age", "18", FilterType::Lt),
+///     Condition::and(vec![Condition::filter("age", "65", FilterType::Gte), !Condition::filter("is_retired", "true", FilterType::Is)]),
+/// ]);
+/// db.from("people").find_many::<Person>().condition(condition).exec().await;
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Condition {
+	Filter {
+		column: String,
+		value: FilterValue,
+		filter_type: FilterType,
+		quantifier: Option<Quantifier>,
+	},
+	And(Vec<Condition>),
+	Or(Vec<Condition>),
+	Not(Box<Condition>),
+}
+
+impl Condition {
+	pub fn filter(column: &str, value: impl Into<FilterValue>, filter_type: FilterType) -> Self {
+		Condition::Filter {
+			column: column.to_string(),
+			value: value.into(),
+			filter_type,
+			quantifier: None,
+		}
+	}
+
+	/// Compare `column` against any or all of `values` e.g. `name.like(any).{O*,P*}`
+	pub fn quantified<V: ToString>(column: &str, values: &[V], filter_type: FilterType, quantifier: Quantifier) -> Self {
+		Condition::Filter {
+			column: column.to_string(),
+			value: FilterValue::Array(values.iter().map(|value| value.to_string()).collect()),
+			filter_type,
+			quantifier: Some(quantifier),
+		}
+	}
+
+	pub fn and(conditions: Vec<Condition>) -> Self {
+		Condition::And(conditions)
+	}
+
+	pub fn or(conditions: Vec<Condition>) -> Self {
+		Condition::Or(conditions)
+	}
+
+	/// Render as a top-level query pair e.g. `("age", "not.gt.18")` or `("or", "(a.eq.1,b.eq.2)")`
+	///
+	/// `path` scopes the condition to an embedded resource e.g. `("orders.or", "(...)")`.
+	pub(crate) fn to_query_pair(&self, path: Option<&str>) -> (String, String) {
+		let (negated, condition) = self.unwrap_not();
+		let not = if negated { "not." } else { "" };
+		let (key, value) = match condition {
+			Condition::Filter {
+				column,
+				value,
+				filter_type,
+				quantifier,
+			} => (
+				column.clone(),
+				format!("{}{}.{}", not, operator(filter_type, quantifier), value.render(filter_type, false)),
+			),
+			Condition::And(conditions) => (format!("{}and", not), group(conditions)),
+			Condition::Or(conditions) => (format!("{}or", not), group(conditions)),
+			Condition::Not(_) => unreachable!("unwrap_not strips every Not"),
+		};
+		match path {
+			Some(path) => (format!("{}.{}", path, key), value),
+			None => (key, value),
+		}
+	}
+
+	/// Render as an operand of a logical operator e.g. `age.not.gt.18` or `not.or(a.eq.1,b.eq.2)`
+	fn to_tree_string(&self) -> String {
+		let (negated, condition) = self.unwrap_not();
+		let not = if negated { "not." } else { "" };
+		match condition {
+			Condition::Filter {
+				column,
+				value,
+				filter_type,
+				quantifier,
+			} => format!(
+				"{}.{}{}.{}",
+				column,
+				not,
+				operator(filter_type, quantifier),
+				value.render(filter_type, true)
+			),
+			Condition::And(conditions) => format!("{}and{}", not, group(conditions)),
+			Condition::Or(conditions) => format!("{}or{}", not, group(conditions)),
+			Condition::Not(_) => unreachable!("unwrap_not strips every Not"),
+		}
+	}
+
+	/// Collapse nested negations, returning whether the remaining condition is negated
+	fn unwrap_not(&self) -> (bool, &Condition) {
+		let mut negated = false;
+		let mut condition = self;
+		while let Condition::Not(inner) = condition {
+			negated = !negated;
+			condition = inner;
+		}
+		(negated, condition)
+	}
+}
+
+impl std::ops::Not for Condition {
+	type Output = Condition;
+
+	fn not(self) -> Self::Output {
+		Condition::Not(Box::new(self))
+	}
+}
+
+fn operator(filter_type: &FilterType, quantifier: &Option<Quantifier>) -> String {
+	match quantifier {
+		Some(quantifier) => format!("{}({})", filter_type, quantifier),
+		None => filter_type.to_string(),
+	}
+}
+
+fn group(conditions: &[Condition]) -> String {
+	format!(
+		"({})",
+		conditions.iter().map(Condition::to_tree_string).collect::<Vec<String>>().join(",")
+	)
+}
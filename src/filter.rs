@@ -1,12 +1,23 @@
 use crate::{
-	builder::PostgrestQuery,
-	handler::{PostgrestError, PostgrestHandler},
+	builder::{profile_headers, Count, PostgrestQuery, Return},
+	condition::Condition,
+	handler::{PostgrestError, PostgrestHandler, PostgrestResponse},
+	http::HttpClient,
+	prefer::{Handling, Preferences},
+	select::{clean_columns, Embed},
+	state::{Executable, Filterable, Filtered, Mutation, Read, Unfiltered, Upsert},
+	transport::Transport,
+	value::FilterValue,
+};
+use reqwest::{
+	header::{HeaderMap, HeaderValue, RANGE},
+	Method,
 };
-use reqwest::{header::HeaderMap, Method};
 use serde::{de::DeserializeOwned, Serialize};
+use std::sync::Arc;
 use url::Url;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum FilterType {
 	Eq,
 	Neq,
@@ -16,7 +27,10 @@ pub enum FilterType {
 	Lte,
 	Like,
 	Ilike,
+	Match,
+	Imatch,
 	Is,
+	IsDistinct,
 	In,
 	Cs,
 	Cd,
@@ -26,10 +40,11 @@ pub enum FilterType {
 	Nxr,
 	Adj,
 	Ov,
-	Fts,
-	Plfts,
-	Phfts,
-	Wfts,
+	/// Full-text search operators, optionally with a text search configuration e.g. `fts(english)`
+	Fts(Option<String>),
+	Plfts(Option<String>),
+	Phfts(Option<String>),
+	Wfts(Option<String>),
 }
 
 impl std::fmt::Display for FilterType {
@@ -43,7 +58,10 @@ impl std::fmt::Display for FilterType {
 			FilterType::Lte => "lte",
 			FilterType::Like => "like",
 			FilterType::Ilike => "ilike",
+			FilterType::Match => "match",
+			FilterType::Imatch => "imatch",
 			FilterType::Is => "is",
+			FilterType::IsDistinct => "isdistinct",
 			FilterType::In => "in",
 			FilterType::Cs => "cs",
 			FilterType::Cd => "cd",
@@ -53,16 +71,112 @@ impl std::fmt::Display for FilterType {
 			FilterType::Nxr => "nxr",
 			FilterType::Adj => "adj",
 			FilterType::Ov => "ov",
-			FilterType::Fts => "fts",
-			FilterType::Plfts => "plfts",
-			FilterType::Phfts => "phfts",
-			FilterType::Wfts => "wfts",
+			FilterType::Fts(_) => "fts",
+			FilterType::Plfts(_) => "plfts",
+			FilterType::Phfts(_) => "phfts",
+			FilterType::Wfts(_) => "wfts",
+		};
+		match self {
+			FilterType::Fts(Some(config)) | FilterType::Plfts(Some(config)) | FilterType::Phfts(Some(config)) | FilterType::Wfts(Some(config)) => {
+				write!(f, "{}({})", lowercase_str, config)
+			}
+			_ => write!(f, "{}", lowercase_str),
+		}
+	}
+}
+
+/// Compare a column against any or all of a list of values e.g. `eq(any).{a,b}`
+///
+/// Supported by `eq`, `like`, `ilike`, `gt`, `gte`, `lt`, `lte`, `match` and `imatch`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Quantifier {
+	Any,
+	All,
+}
+
+impl std::fmt::Display for Quantifier {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let lowercase_str = match self {
+			Quantifier::Any => "any",
+			Quantifier::All => "all",
+		};
+		write!(f, "{}", lowercase_str)
+	}
+}
+
+/// Where NULL values are placed when ordering
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Nulls {
+	First,
+	Last,
+}
+
+impl std::fmt::Display for Nulls {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let lowercase_str = match self {
+			Nulls::First => "nullsfirst",
+			Nulls::Last => "nullslast",
 		};
 		write!(f, "{}", lowercase_str)
 	}
 }
 
-pub struct PostgrestFilter<T, B>
+/// A single column of an `order=` parameter e.g. `created_at.desc.nullslast`
+///
+/// The column can be a JSON path (`data->>age`) or a column of an embedded to-one resource (see `Order::embedded`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Order {
+	pub column: String,
+	pub ascending: bool,
+	pub nulls: Option<Nulls>,
+}
+
+impl Order {
+	pub fn asc(column: &str) -> Self {
+		Order {
+			column: column.to_string(),
+			ascending: true,
+			nulls: None,
+		}
+	}
+
+	pub fn desc(column: &str) -> Self {
+		Order {
+			column: column.to_string(),
+			ascending: false,
+			nulls: None,
+		}
+	}
+
+	pub fn nulls_first(mut self) -> Self {
+		self.nulls = Some(Nulls::First);
+		self
+	}
+
+	pub fn nulls_last(mut self) -> Self {
+		self.nulls = Some(Nulls::Last);
+		self
+	}
+
+	/// Order the top-level rows by a column of an embedded to-one resource (`relation(column)`)
+	pub fn embedded(mut self, relation: &str) -> Self {
+		self.column = format!("{}({})", relation, self.column);
+		self
+	}
+}
+
+impl std::fmt::Display for Order {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}.{}", self.column, if self.ascending { "asc" } else { "desc" })?;
+		if let Some(nulls) = &self.nulls {
+			write!(f, ".{}", nulls)?;
+		}
+		Ok(())
+	}
+}
+
+/// A query on a table or view, `S` is its `state` and decides which methods it has
+pub struct PostgrestFilter<T, B, S = Read>
 where
 	T: Serialize + DeserializeOwned,
 {
@@ -71,152 +185,714 @@ where
 	pub method: Method,
 	pub body: Option<B>,
 	pub query_type: PostgrestQuery,
+	/// Sent as the `Prefer` header, seeded from every `Prefer` header in `headers`
+	pub prefer: Preferences,
+	/// Resources added with `embed`, kept after the columns when `select` is called again
+	pub embeds: Vec<String>,
+	/// Decode the rows PostgREST returns instead of counting the affected rows, see `returning`
+	pub representation: bool,
+	/// Sent as `Accept-Profile` for reads and `Content-Profile` for writes, see `PostgrestClient::schema`
+	pub schema: Option<String>,
+	/// First error hit while building the query, returned by `exec` instead of sending the request
+	pub error: Option<PostgrestError>,
+	/// Let an UPDATE or DELETE without filters through, see `all_rows`
+	pub all_rows: bool,
+	pub transport: Arc<dyn Transport>,
 	pub _marker: std::marker::PhantomData<T>,
+	pub _state: std::marker::PhantomData<S>,
 }
 
-impl<T, B> PostgrestFilter<T, B>
+impl<T, B, S> PostgrestFilter<T, B, S>
 where
 	T: Serialize + DeserializeOwned,
 	B: Serialize + DeserializeOwned,
 {
-	pub fn new(url: Url, method: Method, headers: Option<HeaderMap>, body: Option<B>, query_type: PostgrestQuery) -> Self {
+	pub fn new(url: Url, method: Method, mut headers: Option<HeaderMap>, body: Option<B>, query_type: PostgrestQuery) -> Self {
+		// Every `Prefer` header counts, later ones win like later preferences within a header
+		let mut prefer = Preferences::default();
+		let mut error = None;
+		if let Some(headers) = headers.as_mut() {
+			for value in headers.get_all("Prefer") {
+				match value.to_str() {
+					Ok(value) => prefer.merge(Preferences::parse(value)),
+					Err(_) => {
+						error.get_or_insert(PostgrestError::InvalidHeader {
+							name: "Prefer".to_string(),
+							value: String::from_utf8_lossy(value.as_bytes()).into_owned(),
+						});
+					}
+				}
+			}
+			headers.remove("Prefer");
+		}
+
 		PostgrestFilter {
 			url,
 			headers,
 			method,
 			body,
 			query_type,
+			prefer,
+			embeds: Vec::new(),
+			representation: false,
+			schema: None,
+			error,
+			all_rows: false,
+			transport: Arc::new(HttpClient::default()),
 			_marker: std::marker::PhantomData,
+			_state: std::marker::PhantomData,
 		}
 	}
 
-	// TODO: question this builder pattern for filtering - maybe we can make this better?
-	pub fn eq(mut self, column: &str, value: &str) -> Self {
-		self.url.query_pairs_mut().append_pair(column, format!("eq.{}", value).as_str());
+	/// Merge preferences into the `Prefer` header, the ones set here win over earlier ones
+	pub fn prefer(mut self, prefer: Preferences) -> Self {
+		self.prefer.merge(prefer);
 		self
 	}
 
-	pub fn neq(mut self, column: &str, value: &str) -> Self {
-		self.url.query_pairs_mut().append_pair(column, format!("neq.{}", value).as_str());
+	/// Choose which columns PostgREST should return (`select=`)
+	///
+	/// Accepts a PostgREST column list including aliases (`owner:user_id`), casts (`created_at::text`) and
+	/// JSON paths (`data->owner->>name`). Whitespace outside of double quotes is stripped. On mutations it trims the
+	/// rows `returning` asks for, the query still resolves to the affected row count without it.
+	///
+	/// # Example
+	/// ```ignore
+	/// db.from("users").find_many::<User>().select("id, name, owner:user_id, data->owner->>name").exec().await;
+	/// ```
+	pub fn select(mut self, columns: &str) -> Self {
+		let columns = std::iter::once(clean_columns(columns)).chain(self.embeds.iter().cloned());
+		set_query_param(&mut self.url, "select", &columns.collect::<Vec<String>>().join(","));
 		self
 	}
 
-	pub fn gt(mut self, column: &str, value: &str) -> Self {
-		self.url.query_pairs_mut().append_pair(column, format!("gt.{}", value).as_str());
+	/// Embed a related resource into the response, appended to the current `select` (`*` when none is set)
+	///
+	/// The embedded rows deserialize into nested fields of `T`, e.g. `orders: Vec<Order>` for a one-to-many
+	/// relationship or `customer: Customer` for a many-to-one. A later `select` replaces the columns but keeps the
+	/// embedded resources.
+	///
+	/// # Example
+	/// ```ignore
+	/// db.from("customers").find_many::<Customer>().embed(Embed::new("orders").select("id,total")).exec().await;
+	/// ```
+	pub fn embed(mut self, embed: Embed) -> Self {
+		let columns = get_query_param(&self.url, "select").unwrap_or_else(|| String::from("*"));
+		set_query_param(&mut self.url, "select", &format!("{},{}", columns, embed));
+		self.embeds.push(embed.to_string());
 		self
 	}
 
-	pub fn gte(mut self, column: &str, value: &str) -> Self {
-		self.url.query_pairs_mut().append_pair(column, format!("gte.{}", value).as_str());
-		self
+	/// Move every field into a query with another response type or state
+	fn cast<R, S2>(self) -> PostgrestFilter<R, B, S2>
+	where
+		R: Serialize + DeserializeOwned,
+	{
+		PostgrestFilter {
+			url: self.url,
+			headers: self.headers,
+			method: self.method,
+			body: self.body,
+			query_type: self.query_type,
+			prefer: self.prefer,
+			embeds: self.embeds,
+			representation: self.representation,
+			schema: self.schema,
+			error: self.error,
+			all_rows: self.all_rows,
+			transport: self.transport,
+			_marker: std::marker::PhantomData,
+			_state: std::marker::PhantomData,
+		}
 	}
 
-	pub fn lt(mut self, column: &str, value: &str) -> Self {
-		self.url.query_pairs_mut().append_pair(column, format!("lt.{}", value).as_str());
-		self
+	fn handler(self) -> Result<PostgrestHandler<B>, PostgrestError> {
+		if let Some(err) = self.error {
+			return Err(err);
+		}
+		let guarded = matches!(
+			self.query_type,
+			PostgrestQuery::Update | PostgrestQuery::UpdateMany | PostgrestQuery::Delete | PostgrestQuery::DeleteMany
+		);
+		if guarded && !self.all_rows && !has_filter(&self.url) {
+			return Err(PostgrestError::UnfilteredMutation);
+		}
+
+		let mut headers = profile_headers(self.headers, self.schema.as_deref(), &self.method)?;
+		if !self.prefer.is_empty() {
+			let prefer = self.prefer.to_string();
+			let value = HeaderValue::from_str(&prefer).map_err(|_| PostgrestError::InvalidHeader {
+				name: "Prefer".to_string(),
+				value: prefer,
+			})?;
+			headers.get_or_insert_with(HeaderMap::new).insert("Prefer", value);
+		}
+
+		let mut handler = PostgrestHandler::new(self.url, headers, self.method, self.body, self.query_type);
+		handler.representation = self.representation;
+		handler.transport = self.transport;
+		Ok(handler)
 	}
+}
 
-	pub fn lte(mut self, column: &str, value: &str) -> Self {
-		self.url.query_pairs_mut().append_pair(column, format!("lte.{}", value).as_str());
-		self
+/// Filters, the first one turns an `Unfiltered` mutation into a `Filtered` one
+impl<T, B, S> PostgrestFilter<T, B, S>
+where
+	T: Serialize + DeserializeOwned,
+	B: Serialize + DeserializeOwned,
+	S: Filterable,
+{
+	// TODO: question this builder pattern for filtering - maybe we can make this better?
+	pub fn eq(self, column: &str, value: impl Into<FilterValue>) -> PostgrestFilter<T, B, S::Filtered> {
+		self.filter(column, value, FilterType::Eq)
 	}
 
-	pub fn like(mut self, column: &str, value: &str) -> Self {
-		self.url.query_pairs_mut().append_pair(column, format!("like.{}", value).as_str());
-		self
+	pub fn neq(self, column: &str, value: impl Into<FilterValue>) -> PostgrestFilter<T, B, S::Filtered> {
+		self.filter(column, value, FilterType::Neq)
 	}
 
-	pub fn ilike(mut self, column: &str, value: &str) -> Self {
-		self.url.query_pairs_mut().append_pair(column, format!("ilike.{}", value).as_str());
-		self
+	pub fn gt(self, column: &str, value: impl Into<FilterValue>) -> PostgrestFilter<T, B, S::Filtered> {
+		self.filter(column, value, FilterType::Gt)
 	}
 
-	pub fn is(mut self, column: &str, value: &str) -> Self {
-		self.url.query_pairs_mut().append_pair(column, format!("is.{}", value).as_str());
-		self
+	pub fn gte(self, column: &str, value: impl Into<FilterValue>) -> PostgrestFilter<T, B, S::Filtered> {
+		self.filter(column, value, FilterType::Gte)
 	}
 
-	pub fn in_filter(mut self, column: &str, value: &str) -> Self {
-		self.url.query_pairs_mut().append_pair(column, format!("in.{}", value).as_str());
-		self
+	pub fn lt(self, column: &str, value: impl Into<FilterValue>) -> PostgrestFilter<T, B, S::Filtered> {
+		self.filter(column, value, FilterType::Lt)
+	}
+
+	pub fn lte(self, column: &str, value: impl Into<FilterValue>) -> PostgrestFilter<T, B, S::Filtered> {
+		self.filter(column, value, FilterType::Lte)
+	}
+
+	pub fn like(self, column: &str, value: impl Into<FilterValue>) -> PostgrestFilter<T, B, S::Filtered> {
+		self.filter(column, value, FilterType::Like)
+	}
+
+	pub fn ilike(self, column: &str, value: impl Into<FilterValue>) -> PostgrestFilter<T, B, S::Filtered> {
+		self.filter(column, value, FilterType::Ilike)
+	}
+
+	/// Match a regular expression, case sensitive (`column=match.regex`)
+	pub fn match_filter(self, column: &str, value: impl Into<FilterValue>) -> PostgrestFilter<T, B, S::Filtered> {
+		self.filter(column, value, FilterType::Match)
+	}
+
+	/// Match a regular expression, case insensitive (`column=imatch.regex`)
+	pub fn imatch(self, column: &str, value: impl Into<FilterValue>) -> PostgrestFilter<T, B, S::Filtered> {
+		self.filter(column, value, FilterType::Imatch)
+	}
+
+	pub fn is(self, column: &str, value: impl Into<FilterValue>) -> PostgrestFilter<T, B, S::Filtered> {
+		self.filter(column, value, FilterType::Is)
+	}
+
+	/// `IS DISTINCT FROM`, like `neq` but treats NULL as a comparable value (`column=isdistinct.value`)
+	pub fn is_distinct(self, column: &str, value: impl Into<FilterValue>) -> PostgrestFilter<T, B, S::Filtered> {
+		self.filter(column, value, FilterType::IsDistinct)
+	}
+
+	pub fn in_filter(self, column: &str, value: impl Into<FilterValue>) -> PostgrestFilter<T, B, S::Filtered> {
+		self.filter(column, value, FilterType::In)
+	}
+
+	pub fn cs(self, column: &str, value: impl Into<FilterValue>) -> PostgrestFilter<T, B, S::Filtered> {
+		self.filter(column, value, FilterType::Cs)
 	}
 
-	pub fn cs(mut self, column: &str, value: &str) -> Self {
-		self.url.query_pairs_mut().append_pair(column, format!("cs.{}", value).as_str());
+	pub fn cd(self, column: &str, value: impl Into<FilterValue>) -> PostgrestFilter<T, B, S::Filtered> {
+		self.filter(column, value, FilterType::Cd)
+	}
+
+	pub fn sl(self, column: &str, value: impl Into<FilterValue>) -> PostgrestFilter<T, B, S::Filtered> {
+		self.filter(column, value, FilterType::Sl)
+	}
+
+	pub fn sr(self, column: &str, value: impl Into<FilterValue>) -> PostgrestFilter<T, B, S::Filtered> {
+		self.filter(column, value, FilterType::Sr)
+	}
+
+	pub fn nxl(self, column: &str, value: impl Into<FilterValue>) -> PostgrestFilter<T, B, S::Filtered> {
+		self.filter(column, value, FilterType::Nxl)
+	}
+
+	pub fn nxr(self, column: &str, value: impl Into<FilterValue>) -> PostgrestFilter<T, B, S::Filtered> {
+		self.filter(column, value, FilterType::Nxr)
+	}
+
+	pub fn adj(self, column: &str, value: impl Into<FilterValue>) -> PostgrestFilter<T, B, S::Filtered> {
+		self.filter(column, value, FilterType::Adj)
+	}
+
+	pub fn ov(self, column: &str, value: impl Into<FilterValue>) -> PostgrestFilter<T, B, S::Filtered> {
+		self.filter(column, value, FilterType::Ov)
+	}
+
+	/// Full-text search, `config` picks a text search configuration e.g. `Some("english")` for `fts(english)`
+	pub fn fts(self, column: &str, value: impl Into<FilterValue>, config: Option<&str>) -> PostgrestFilter<T, B, S::Filtered> {
+		self.filter(column, value, FilterType::Fts(config.map(String::from)))
+	}
+
+	pub fn plfts(self, column: &str, value: impl Into<FilterValue>, config: Option<&str>) -> PostgrestFilter<T, B, S::Filtered> {
+		self.filter(column, value, FilterType::Plfts(config.map(String::from)))
+	}
+
+	pub fn phfts(self, column: &str, value: impl Into<FilterValue>, config: Option<&str>) -> PostgrestFilter<T, B, S::Filtered> {
+		self.filter(column, value, FilterType::Phfts(config.map(String::from)))
+	}
+
+	pub fn wfts(self, column: &str, value: impl Into<FilterValue>, config: Option<&str>) -> PostgrestFilter<T, B, S::Filtered> {
+		self.filter(column, value, FilterType::Wfts(config.map(String::from)))
+	}
+
+	// TODO: add more filters
+
+	pub fn filter(self, column: &str, value: impl Into<FilterValue>, filter_method: FilterType) -> PostgrestFilter<T, B, S::Filtered> {
+		self.condition(Condition::filter(column, value, filter_method))
+	}
+
+	/// Add an `eq` filter for every present (non-`None`) field of a struct or entry of a map
+	///
+	/// `exec` fails with `PostgrestError::ArgumentEncoding` if `values` does not serialize into a struct or map.
+	///
+	/// # Example
+	/// ```ignore
+	/// #[derive(Serialize)]
+	/// struct Search { name: Option<String>, age: Option<i32> }
+	///
+	/// // name=eq.darren (age is skipped)
+	/// db.from("users").find_many::<User>().match_columns(&Search { name: Some("darren".into()), age: None });
+	/// ```
+	pub fn match_columns<M: Serialize>(self, values: &M) -> PostgrestFilter<T, B, S::Filtered> {
+		let mut filter = self.cast();
+		let values = match serde_json::to_value(values) {
+			Ok(serde_json::Value::Object(values)) => values,
+			Ok(_) => {
+				filter
+					.error
+					.get_or_insert(PostgrestError::ArgumentEncoding("match_columns expects a struct or map".to_string()));
+				return filter;
+			}
+			Err(err) => {
+				filter.error.get_or_insert(PostgrestError::ArgumentEncoding(err.to_string()));
+				return filter;
+			}
+		};
+		for (column, value) in values.iter().filter(|(_, value)| !value.is_null()) {
+			let (key, value) = Condition::filter(column, FilterValue::from_json(value), FilterType::Eq).to_query_pair(None);
+			filter.url.query_pairs_mut().append_pair(&key, &value);
+		}
+		filter
+	}
+
+	/// Compare a column against any or all of `values` (`column=op(any).{a,b}`)
+	///
+	/// # Example
+	/// ```ignore
+	/// // name=like(any).{O*,P*}
+	/// db.from("users").find_many::<User>().quantified_filter("name", &["O*", "P*"], FilterType::Like, Quantifier::Any);
+	/// ```
+	pub fn quantified_filter<V: ToString>(
+		self,
+		column: &str,
+		values: &[V],
+		filter_method: FilterType,
+		quantifier: Quantifier,
+	) -> PostgrestFilter<T, B, S::Filtered> {
+		self.condition(Condition::quantified(column, values, filter_method, quantifier))
+	}
+
+	pub fn eq_any<V: ToString>(self, column: &str, values: &[V]) -> PostgrestFilter<T, B, S::Filtered> {
+		self.quantified_filter(column, values, FilterType::Eq, Quantifier::Any)
+	}
+
+	pub fn eq_all<V: ToString>(self, column: &str, values: &[V]) -> PostgrestFilter<T, B, S::Filtered> {
+		self.quantified_filter(column, values, FilterType::Eq, Quantifier::All)
+	}
+
+	pub fn like_any<V: ToString>(self, column: &str, values: &[V]) -> PostgrestFilter<T, B, S::Filtered> {
+		self.quantified_filter(column, values, FilterType::Like, Quantifier::Any)
+	}
+
+	pub fn like_all<V: ToString>(self, column: &str, values: &[V]) -> PostgrestFilter<T, B, S::Filtered> {
+		self.quantified_filter(column, values, FilterType::Like, Quantifier::All)
+	}
+
+	pub fn ilike_any<V: ToString>(self, column: &str, values: &[V]) -> PostgrestFilter<T, B, S::Filtered> {
+		self.quantified_filter(column, values, FilterType::Ilike, Quantifier::Any)
+	}
+
+	pub fn ilike_all<V: ToString>(self, column: &str, values: &[V]) -> PostgrestFilter<T, B, S::Filtered> {
+		self.quantified_filter(column, values, FilterType::Ilike, Quantifier::All)
+	}
+
+	pub fn gt_any<V: ToString>(self, column: &str, values: &[V]) -> PostgrestFilter<T, B, S::Filtered> {
+		self.quantified_filter(column, values, FilterType::Gt, Quantifier::Any)
+	}
+
+	pub fn gt_all<V: ToString>(self, column: &str, values: &[V]) -> PostgrestFilter<T, B, S::Filtered> {
+		self.quantified_filter(column, values, FilterType::Gt, Quantifier::All)
+	}
+
+	pub fn gte_any<V: ToString>(self, column: &str, values: &[V]) -> PostgrestFilter<T, B, S::Filtered> {
+		self.quantified_filter(column, values, FilterType::Gte, Quantifier::Any)
+	}
+
+	pub fn gte_all<V: ToString>(self, column: &str, values: &[V]) -> PostgrestFilter<T, B, S::Filtered> {
+		self.quantified_filter(column, values, FilterType::Gte, Quantifier::All)
+	}
+
+	pub fn lt_any<V: ToString>(self, column: &str, values: &[V]) -> PostgrestFilter<T, B, S::Filtered> {
+		self.quantified_filter(column, values, FilterType::Lt, Quantifier::Any)
+	}
+
+	pub fn lt_all<V: ToString>(self, column: &str, values: &[V]) -> PostgrestFilter<T, B, S::Filtered> {
+		self.quantified_filter(column, values, FilterType::Lt, Quantifier::All)
+	}
+
+	pub fn lte_any<V: ToString>(self, column: &str, values: &[V]) -> PostgrestFilter<T, B, S::Filtered> {
+		self.quantified_filter(column, values, FilterType::Lte, Quantifier::Any)
+	}
+
+	pub fn lte_all<V: ToString>(self, column: &str, values: &[V]) -> PostgrestFilter<T, B, S::Filtered> {
+		self.quantified_filter(column, values, FilterType::Lte, Quantifier::All)
+	}
+
+	pub fn match_any<V: ToString>(self, column: &str, values: &[V]) -> PostgrestFilter<T, B, S::Filtered> {
+		self.quantified_filter(column, values, FilterType::Match, Quantifier::Any)
+	}
+
+	pub fn match_all<V: ToString>(self, column: &str, values: &[V]) -> PostgrestFilter<T, B, S::Filtered> {
+		self.quantified_filter(column, values, FilterType::Match, Quantifier::All)
+	}
+
+	pub fn imatch_any<V: ToString>(self, column: &str, values: &[V]) -> PostgrestFilter<T, B, S::Filtered> {
+		self.quantified_filter(column, values, FilterType::Imatch, Quantifier::Any)
+	}
+
+	pub fn imatch_all<V: ToString>(self, column: &str, values: &[V]) -> PostgrestFilter<T, B, S::Filtered> {
+		self.quantified_filter(column, values, FilterType::Imatch, Quantifier::All)
+	}
+
+	/// Match rows where at least one of the conditions holds (`or=(...)`)
+	pub fn or(self, conditions: Vec<Condition>) -> PostgrestFilter<T, B, S::Filtered> {
+		self.condition(Condition::Or(conditions))
+	}
+
+	/// Match rows where all of the conditions hold (`and=(...)`), useful when nested inside `or` or `not`
+	pub fn and(self, conditions: Vec<Condition>) -> PostgrestFilter<T, B, S::Filtered> {
+		self.condition(Condition::And(conditions))
+	}
+
+	/// Negate any operator (`column=not.op.value`)
+	pub fn not(self, column: &str, value: impl Into<FilterValue>, filter_method: FilterType) -> PostgrestFilter<T, B, S::Filtered> {
+		self.condition(!Condition::filter(column, value, filter_method))
+	}
+
+	/// Apply an arbitrary tree of `Condition`s
+	pub fn condition(self, condition: Condition) -> PostgrestFilter<T, B, S::Filtered> {
+		let mut filter = self.cast();
+		let (key, value) = condition.to_query_pair(None);
+		filter.url.query_pairs_mut().append_pair(&key, &value);
+		filter
+	}
+}
+
+impl<T, B> PostgrestFilter<T, B, Read>
+where
+	T: Serialize + DeserializeOwned,
+	B: Serialize + DeserializeOwned,
+{
+	/// Order the rows, repeated calls add further columns to the same `order=` parameter
+	///
+	/// # Example
+	/// ```ignore
+	/// // order=created_at.desc.nullslast,author(name).asc
+	/// db.from("posts")
+	///     .find_many::<Post>()
+	///     .order(Order::desc("created_at").nulls_last())
+	///     .order(Order::asc("name").embedded("author"))
+	///     .exec()
+	///     .await;
+	/// ```
+	pub fn order(mut self, order: Order) -> Self {
+		append_order(&mut self.url, "order", &order);
 		self
 	}
 
-	pub fn cd(mut self, column: &str, value: &str) -> Self {
-		self.url.query_pairs_mut().append_pair(column, format!("cd.{}", value).as_str());
+	/// Limit the number of rows returned (`limit=n`)
+	pub fn limit(mut self, limit: usize) -> Self {
+		set_query_param(&mut self.url, "limit", &limit.to_string());
 		self
 	}
 
-	pub fn sl(mut self, column: &str, value: &str) -> Self {
-		self.url.query_pairs_mut().append_pair(column, format!("sl.{}", value).as_str());
+	/// Skip the first `offset` rows (`offset=n`), usually paired with `order` and `limit`
+	pub fn offset(mut self, offset: usize) -> Self {
+		set_query_param(&mut self.url, "offset", &offset.to_string());
 		self
 	}
 
-	pub fn sr(mut self, column: &str, value: &str) -> Self {
-		self.url.query_pairs_mut().append_pair(column, format!("sr.{}", value).as_str());
+	/// Only return rows `from` through `to` (zero-based, inclusive) using the `Range` and `Range-Unit: items` headers
+	///
+	/// Use `exec_response` to read back the `Content-Range` PostgREST responds with.
+	///
+	/// # Example
+	/// ```ignore
+	/// // Range: 0-24
+	/// let res = db.from("users").find_many::<User>().range(0, 24).exec_response().await?;
+	/// println!("{:?} of {:?}", res.range, res.count);
+	/// ```
+	pub fn range(mut self, from: usize, to: usize) -> Self {
+		let mut headers = self.headers.unwrap_or_default();
+		headers.insert("Range-Unit", HeaderValue::from_static("items"));
+		if let Ok(value) = HeaderValue::from_str(&format!("{}-{}", from, to)) {
+			headers.insert(RANGE, value);
+		}
+		self.headers = Some(headers);
 		self
 	}
 
-	pub fn nxl(mut self, column: &str, value: &str) -> Self {
-		self.url.query_pairs_mut().append_pair(column, format!("nxl.{}", value).as_str());
+	/// Apply an arbitrary tree of `Condition`s to an embedded resource (`path.or=(...)`)
+	pub fn embedded_condition(mut self, path: &str, condition: Condition) -> Self {
+		let (key, value) = condition.to_query_pair(Some(path));
+		self.url.query_pairs_mut().append_pair(&key, &value);
 		self
 	}
 
-	pub fn nxr(mut self, column: &str, value: &str) -> Self {
-		self.url.query_pairs_mut().append_pair(column, format!("nxr.{}", value).as_str());
+	/// Apply a filter to an embedded resource instead of the top-level relation (`path.column=op.value`)
+	///
+	/// `path` is the embedded resource name (or alias), use dots for nested embeds e.g. `orders.items`.
+	///
+	/// # Example
+	/// ```ignore
+	/// // orders.status=eq.paid
+	/// db.from("customers").find_many::<Customer>().embed(Embed::new("orders")).embedded_filter("orders", "status", "paid", FilterType::Eq);
+	/// ```
+	pub fn embedded_filter(self, path: &str, column: &str, value: impl Into<FilterValue>, filter_method: FilterType) -> Self {
+		self.embedded_condition(path, Condition::filter(column, value, filter_method))
+	}
+
+	/// Order the rows of an embedded resource (`path.order=column.asc`), repeated calls add further columns
+	pub fn embedded_order(mut self, path: &str, order: Order) -> Self {
+		append_order(&mut self.url, &format!("{}.order", path), &order);
 		self
 	}
 
-	pub fn adj(mut self, column: &str, value: &str) -> Self {
-		self.url.query_pairs_mut().append_pair(column, format!("adj.{}", value).as_str());
+	/// Limit the number of rows returned for each embedded resource (`path.limit=n`)
+	pub fn embedded_limit(mut self, path: &str, limit: usize) -> Self {
+		set_query_param(&mut self.url, &format!("{}.limit", path), &limit.to_string());
 		self
 	}
 
-	pub fn ov(mut self, column: &str, value: &str) -> Self {
-		self.url.query_pairs_mut().append_pair(column, format!("ov.{}", value).as_str());
+	/// Skip rows of an embedded resource (`path.offset=n`)
+	pub fn embedded_offset(mut self, path: &str, offset: usize) -> Self {
+		set_query_param(&mut self.url, &format!("{}.offset", path), &offset.to_string());
 		self
 	}
 
-	pub fn fts(mut self, column: &str, value: &str) -> Self {
-		self.url.query_pairs_mut().append_pair(column, format!("fts.{}", value).as_str());
+	/// Only return top-level rows without any matching embedded rows (`path=is.null`), an anti-join
+	pub fn embedded_is_null(mut self, path: &str) -> Self {
+		self.url.query_pairs_mut().append_pair(path, "is.null");
 		self
 	}
 
-	pub fn plfts(mut self, column: &str, value: &str) -> Self {
-		self.url.query_pairs_mut().append_pair(column, format!("plfts.{}", value).as_str());
+	/// Only return top-level rows with at least one matching embedded row (`path=not.is.null`)
+	pub fn embedded_not_null(mut self, path: &str) -> Self {
+		self.url.query_pairs_mut().append_pair(path, "not.is.null");
 		self
 	}
 
-	pub fn phfts(mut self, column: &str, value: &str) -> Self {
-		self.url.query_pairs_mut().append_pair(column, format!("phfts.{}", value).as_str());
+	/// Count the rows matching the filters without transferring them (`HEAD` with `Prefer: count=...`)
+	///
+	/// Fails with `PostgrestError::InvalidContentRange` when the response has no total in `Content-Range`.
+	///
+	/// # Example
+	/// ```ignore
+	/// let adults = db.from("users").find_many::<User>().gte("age", 18).count(Count::Exact).await?;
+	/// ```
+	pub async fn count(self, count: Count) -> Result<u64, PostgrestError> {
+		self.head(PostgrestQuery::Count, Some(count))?.exec().await
+	}
+
+	pub fn count_blocking(self, count: Count) -> Result<u64, PostgrestError> {
+		self.head(PostgrestQuery::Count, Some(count))?.exec_blocking()
+	}
+
+	/// Whether any row matches the filters, a `HEAD` for at most one row that doesn't count the others
+	///
+	/// # Example
+	/// ```ignore
+	/// let taken = db.from("users").find_many::<User>().eq("email", email).exists().await?;
+	/// ```
+	pub async fn exists(self) -> Result<bool, PostgrestError> {
+		self.limit(1).head(PostgrestQuery::Exists, None)?.exec::<u64>().await.map(|rows| rows > 0)
+	}
+
+	pub fn exists_blocking(self) -> Result<bool, PostgrestError> {
+		self.limit(1)
+			.head(PostgrestQuery::Exists, None)?
+			.exec_blocking::<u64>()
+			.map(|rows| rows > 0)
+	}
+
+	/// Turn the query into a `HEAD` request, its rows are counted from `Content-Range`
+	fn head(mut self, query_type: PostgrestQuery, count: Option<Count>) -> Result<PostgrestHandler<B>, PostgrestError> {
+		self.method = Method::HEAD;
+		self.body = None;
+		self.query_type = query_type;
+		self.prefer.count = count;
+		self.handler()
+	}
+}
+
+impl<T, B, S> PostgrestFilter<T, B, S>
+where
+	T: Serialize + DeserializeOwned,
+	B: Serialize + DeserializeOwned,
+	S: Mutation,
+{
+	/// Set how much PostgREST returns for a mutation (`Prefer: return=...`)
+	///
+	/// Mutations resolve to the number of affected rows regardless, use `returning` to get the rows. After
+	/// `returning` anything but `Return::Representation` fails with `PostgrestError::ArgumentEncoding`, as there would
+	/// be no rows to decode.
+	pub fn prefer_return(mut self, value: Return) -> Self {
+		if self.representation && value != Return::Representation {
+			self.error.get_or_insert(PostgrestError::ArgumentEncoding(format!(
+				"return={} conflicts with returning(), which needs return=representation",
+				value
+			)));
+		}
+		self.prefer.returning = Some(value);
 		self
 	}
 
-	pub fn wfts(mut self, column: &str, value: &str) -> Self {
-		self.url.query_pairs_mut().append_pair(column, format!("wfts.{}", value).as_str());
+	/// Return the affected rows of a mutation (`Prefer: return=representation`) instead of their count
+	///
+	/// # Example
+	/// ```ignore
+	/// let users: Vec<User> = db.from("users").create_many(users, None, None).returning::<User>().select("id,name").exec().await?;
+	/// ```
+	pub fn returning<R>(self) -> PostgrestFilter<Vec<R>, B, S>
+	where
+		R: Serialize + DeserializeOwned,
+	{
+		let mut filter = self.prefer_return(Return::Representation);
+		filter.representation = true;
+		filter.cast()
+	}
+}
+
+/// Updates and deletes
+impl<T, B, S> PostgrestFilter<T, B, S>
+where
+	T: Serialize + DeserializeOwned,
+	B: Serialize + DeserializeOwned,
+	S: Mutation + Filterable,
+{
+	/// Reject the mutation server-side when it would affect more than `max` rows (`Prefer: max-affected=n, handling=strict`)
+	///
+	/// PostgREST rolls the mutation back and `exec` fails with `PostgrestError::MaxAffectedExceeded`.
+	///
+	/// # Example
+	/// ```ignore
+	/// db.from("users").update(patch, None).eq("team_id", 7).max_affected(50).exec().await?;
+	/// ```
+	pub fn max_affected(mut self, max: u64) -> Self {
+		self.prefer.max_affected = Some(max);
+		self.prefer.handling = Some(Handling::Strict);
 		self
 	}
+}
 
-	// TODO: add more filters
+impl<T, B> PostgrestFilter<T, B, Unfiltered>
+where
+	T: Serialize + DeserializeOwned,
+	B: Serialize + DeserializeOwned,
+{
+	/// Allow an UPDATE or DELETE to affect every row
+	///
+	/// Without it, an update or delete with no filters fails with `PostgrestError::UnfilteredMutation` before
+	/// anything is sent.
+	///
+	/// # Example
+	/// ```ignore
+	/// db.from("sessions").delete::<Session>(None).all_rows().exec().await?;
+	/// ```
+	pub fn all_rows(mut self) -> PostgrestFilter<T, B, Filtered> {
+		self.all_rows = true;
+		self.cast()
+	}
+}
 
-	pub fn filter(mut self, column: &str, value: &str, filter_method: FilterType) -> Self {
-		self.url
-			.query_pairs_mut()
-			.append_pair(column, format!("{}.{}", filter_method, value).as_str());
+impl<T, B> PostgrestFilter<T, B, Upsert>
+where
+	T: Serialize + DeserializeOwned,
+	B: Serialize + DeserializeOwned,
+{
+	/// Columns of the unique constraint that decides what's a duplicate (`on_conflict=...`), the primary key by default
+	///
+	/// # Example
+	/// ```ignore
+	/// db.from("users").upsert(user, None, None, None, None).on_conflict("email").exec().await?;
+	/// ```
+	pub fn on_conflict(mut self, columns: &str) -> Self {
+		set_query_param(&mut self.url, "on_conflict", &clean_columns(columns));
 		self
 	}
+}
 
+impl<T, B, S> PostgrestFilter<T, B, S>
+where
+	T: Serialize + DeserializeOwned,
+	B: Serialize + DeserializeOwned,
+	S: Executable,
+{
 	pub fn exec_blocking(self) -> Result<T, PostgrestError> {
-		let handler = PostgrestHandler::new(self.url, self.headers, self.method, self.body, self.query_type);
-		handler.exec_blocking()
+		self.handler()?.exec_blocking()
 	}
 
 	pub async fn exec(self) -> Result<T, PostgrestError> {
-		let handler = PostgrestHandler::new(self.url, self.headers, self.method, self.body, self.query_type);
-		handler.exec().await
+		self.handler()?.exec().await
 	}
+
+	pub fn exec_blocking_response(self) -> Result<PostgrestResponse<T>, PostgrestError> {
+		self.handler()?.exec_blocking_response()
+	}
+
+	pub async fn exec_response(self) -> Result<PostgrestResponse<T>, PostgrestError> {
+		self.handler()?.exec_response().await
+	}
+}
+
+/// Query parameters (top-level or embedded, e.g. `orders.limit`) that shape the response instead of choosing rows
+const MODIFIERS: [&str; 6] = ["select", "order", "limit", "offset", "on_conflict", "columns"];
+
+fn has_filter(url: &Url) -> bool {
+	url.query_pairs()
+		.any(|(key, _)| !MODIFIERS.contains(&key.rsplit('.').next().unwrap_or_default()))
+}
+
+pub(crate) fn get_query_param(url: &Url, key: &str) -> Option<String> {
+	url.query_pairs().find(|(k, _)| k == key).map(|(_, v)| v.into_owned())
+}
+
+fn append_order(url: &mut Url, key: &str, order: &Order) {
+	let value = match get_query_param(url, key) {
+		Some(existing) => format!("{},{}", existing, order),
+		None => order.to_string(),
+	};
+	set_query_param(url, key, &value);
+}
+
+/// Replace any existing values of `key` in the query string with a single `key=value` pair
+pub(crate) fn set_query_param(url: &mut Url, key: &str, value: &str) {
+	let pairs: Vec<(String, String)> = url
+		.query_pairs()
+		.filter(|(k, _)| k != key)
+		.map(|(k, v)| (k.into_owned(), v.into_owned()))
+		.collect();
+	url.query_pairs_mut().clear().extend_pairs(pairs).append_pair(key, value);
 }
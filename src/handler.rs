@@ -1,13 +1,215 @@
-use crate::builder::PostgrestQuery;
-use reqwest::{blocking::Client as BlockingClient, header::{HeaderMap, USER_AGENT as REQWEST_USER_AGENT}, Client};
+use crate::{
+	builder::PostgrestQuery,
+	http::HttpClient,
+	transport::{Transport, TransportRequest},
+};
+use reqwest::{
+	header::{HeaderMap, HeaderValue, CONTENT_RANGE, CONTENT_TYPE, USER_AGENT as REQWEST_USER_AGENT},
+	StatusCode,
+};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::Value;
+use std::sync::Arc;
 use url::Url;
 
+/// Everything that can go wrong executing a query
+///
+/// PostgREST and PostgreSQL errors that callers commonly branch on get their own variant, any other error response
+/// is returned as-is in `PostgrestErrorResponse`.
+///
+/// # Example
+/// ```ignore
+/// match db.from("users").create(user, None, None).exec().await {
+///     Err(err @ PostgrestError::UniqueViolation(_)) => println!("already exists ({:?})", err.constraint()),
+///     Err(err) => return Err(err.into()),
+///     Ok(_) => {}
+/// }
+/// ```
 #[derive(Debug)]
 pub enum PostgrestError {
+	/// `23505`, e.g. inserting a duplicate key, see `constraint`
+	UniqueViolation(PostgrestErrorResponse),
+	/// `23503`, a referenced row is missing or still referenced, see `constraint`
+	ForeignKeyViolation(PostgrestErrorResponse),
+	/// `23514`, a `CHECK` constraint failed, see `constraint`
+	CheckViolation(PostgrestErrorResponse),
+	/// `23502`, see `column` for the column that was null
+	NotNullViolation(PostgrestErrorResponse),
+	/// Any other integrity constraint violation (SQLSTATE class `23`), e.g. `23P01` for an exclusion constraint
+	IntegrityViolation(PostgrestErrorResponse),
+	/// `42501`, missing grants or a row-level security policy rejected the row
+	PermissionDenied(PostgrestErrorResponse),
+	/// `PGRST301`/`PGRST303` when the JWT's `exp` claim is in the past
+	JwtExpired(PostgrestErrorResponse),
+	/// `PGRST301`-`PGRST303`, the JWT is missing, invalid or anonymous access is disabled
+	Unauthorized(PostgrestErrorResponse),
+	/// The table, view, function or column doesn't exist or isn't exposed (e.g. `PGRST205`, `PGRST202`, `42P01`)
+	NotFound(PostgrestErrorResponse),
+	/// `PGRST116`, a singular query (e.g. `find_unique`) matched no rows
+	RowNotFound(PostgrestErrorResponse),
+	/// `PGRST116`, a singular query (e.g. `find_unique`) matched more than one row
+	MultipleRows(PostgrestErrorResponse),
+	/// `PGRST124`, the mutation would have affected more rows than `max_affected` allows and was rolled back
+	MaxAffectedExceeded(PostgrestErrorResponse),
+	/// `PGRST201`, more than one relationship matches an embed, disambiguate it with `Embed::hint`
+	AmbiguousEmbed(PostgrestErrorResponse),
+	/// Any other error response, including server misconfiguration such as `PGRST300` (no JWT secret configured)
 	PostgrestErrorResponse(PostgrestErrorResponse),
 	ReqwestError(reqwest::Error),
+	SerdeError(serde_json::Error),
+	/// Socket I/O or a malformed response, from transports that speak HTTP themselves (e.g. `UnixSocketTransport`)
+	IoError(std::io::Error),
+	/// The URL given to `PostgrestClient::new` can't be parsed or can't have a path
+	InvalidUrl(String),
+	/// A header value (e.g. the schema or a `Prefer` preference) contains characters not allowed in headers
+	InvalidHeader {
+		name: String,
+		value: String,
+	},
+	/// The server (or a proxy in front of it) responded with an error that isn't PostgREST's JSON, e.g. an HTML 502
+	/// or a JSON body without a `code`
+	NonJsonErrorBody {
+		status: StatusCode,
+		body: String,
+	},
+	/// Arguments, filter values or preferences that can't be encoded into the request
+	ArgumentEncoding(String),
+	/// The `Content-Range` response header is missing (empty), can't be parsed, describes an impossible range such as
+	/// `5-2/*` or lacks the total a count needs
+	InvalidContentRange(String),
+	/// An UPDATE or DELETE without filters, which would affect every row, call `all_rows` if that's intended
+	UnfilteredMutation,
+}
+
+impl PostgrestError {
+	/// The error body PostgREST responded with, `None` for transport and decoding errors
+	pub fn response(&self) -> Option<&PostgrestErrorResponse> {
+		match self {
+			PostgrestError::UniqueViolation(response)
+			| PostgrestError::ForeignKeyViolation(response)
+			| PostgrestError::CheckViolation(response)
+			| PostgrestError::NotNullViolation(response)
+			| PostgrestError::IntegrityViolation(response)
+			| PostgrestError::PermissionDenied(response)
+			| PostgrestError::JwtExpired(response)
+			| PostgrestError::Unauthorized(response)
+			| PostgrestError::NotFound(response)
+			| PostgrestError::RowNotFound(response)
+			| PostgrestError::MultipleRows(response)
+			| PostgrestError::MaxAffectedExceeded(response)
+			| PostgrestError::AmbiguousEmbed(response)
+			| PostgrestError::PostgrestErrorResponse(response) => Some(response),
+			_ => None,
+		}
+	}
+
+	/// HTTP status of the error response, `None` for transport and decoding errors
+	pub fn status(&self) -> Option<StatusCode> {
+		match self {
+			PostgrestError::NonJsonErrorBody { status, .. } => Some(*status),
+			_ => self.response()?.status,
+		}
+	}
+
+	/// The `PGRST*` or SQLSTATE code of the error response
+	pub fn code(&self) -> Option<&str> {
+		self.response()?.code.as_deref()
+	}
+
+	/// Name of the violated constraint of a unique, foreign key or check violation
+	pub fn constraint(&self) -> Option<String> {
+		match self {
+			PostgrestError::UniqueViolation(response) | PostgrestError::ForeignKeyViolation(response) | PostgrestError::CheckViolation(response) => {
+				last_quoted(response.message.as_deref()?)
+			}
+			_ => None,
+		}
+	}
+
+	/// Name of the column a not-null violation was raised for
+	pub fn column(&self) -> Option<String> {
+		match self {
+			PostgrestError::NotNullViolation(response) => first_quoted(response.message.as_deref()?),
+			_ => None,
+		}
+	}
+}
+
+impl std::fmt::Display for PostgrestError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			PostgrestError::ReqwestError(err) => write!(f, "request failed: {}", err),
+			PostgrestError::SerdeError(err) => write!(f, "invalid JSON: {}", err),
+			PostgrestError::IoError(err) => write!(f, "transport failed: {}", err),
+			PostgrestError::InvalidUrl(err) => write!(f, "invalid URL: {}", err),
+			PostgrestError::InvalidHeader { name, value } => write!(f, "invalid value for the {} header: {:?}", name, value),
+			PostgrestError::NonJsonErrorBody { status, body } => write!(f, "{}: {}", status, body),
+			PostgrestError::ArgumentEncoding(err) => write!(f, "can't encode arguments: {}", err),
+			PostgrestError::InvalidContentRange(range) if range.is_empty() => write!(f, "missing Content-Range header"),
+			PostgrestError::InvalidContentRange(range) => write!(f, "invalid Content-Range header: {:?}", range),
+			PostgrestError::UnfilteredMutation => write!(f, "refusing to update or delete every row without all_rows()"),
+			_ => match self.response() {
+				Some(response) => write!(f, "{}", response),
+				None => Ok(()),
+			},
+		}
+	}
+}
+
+impl std::error::Error for PostgrestError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			PostgrestError::ReqwestError(err) => Some(err),
+			PostgrestError::SerdeError(err) => Some(err),
+			PostgrestError::IoError(err) => Some(err),
+			_ => None,
+		}
+	}
+}
+
+/// Pick the variant for an error response from its `PGRST*` or SQLSTATE code
+///
+/// https://postgrest.org/en/stable/references/errors.html
+impl From<PostgrestErrorResponse> for PostgrestError {
+	fn from(response: PostgrestErrorResponse) -> Self {
+		let message = response.message.as_deref().unwrap_or_default();
+		match response.code.as_deref().unwrap_or_default() {
+			"23505" => PostgrestError::UniqueViolation(response),
+			"23503" => PostgrestError::ForeignKeyViolation(response),
+			"23514" => PostgrestError::CheckViolation(response),
+			"23502" => PostgrestError::NotNullViolation(response),
+			code if code.starts_with("23") => PostgrestError::IntegrityViolation(response),
+			"42501" => PostgrestError::PermissionDenied(response),
+			"PGRST301" | "PGRST303" if message.to_lowercase().contains("expired") => PostgrestError::JwtExpired(response),
+			"PGRST301" | "PGRST302" | "PGRST303" => PostgrestError::Unauthorized(response),
+			"PGRST200" | "PGRST202" | "PGRST204" | "PGRST205" | "42P01" | "42883" | "42703" => PostgrestError::NotFound(response),
+			"PGRST116" if matches_zero_rows(&response) => PostgrestError::RowNotFound(response),
+			"PGRST116" => PostgrestError::MultipleRows(response),
+			"PGRST124" => PostgrestError::MaxAffectedExceeded(response),
+			"PGRST201" => PostgrestError::AmbiguousEmbed(response),
+			_ => PostgrestError::PostgrestErrorResponse(response),
+		}
+	}
+}
+
+/// `PGRST116` details read e.g. `The result contains 0 rows` or `Results contain 2 rows, ... requires 1 row`
+fn matches_zero_rows(response: &PostgrestErrorResponse) -> bool {
+	match &response.details {
+		Some(Value::String(details)) => details.contains(" 0 rows"),
+		_ => false,
+	}
+}
+
+/// PostgreSQL quotes identifiers in its messages, e.g. `null value in column "name" of relation "users" ...`
+fn first_quoted(message: &str) -> Option<String> {
+	let (_, rest) = message.split_once('"')?;
+	rest.split_once('"').map(|(quoted, _)| quoted.to_string())
+}
+
+/// e.g. `duplicate key value violates unique constraint "users_email_key"`
+fn last_quoted(message: &str) -> Option<String> {
+	let (rest, _) = message.rsplit_once('"')?;
+	rest.rsplit_once('"').map(|(_, quoted)| quoted.to_string())
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -16,6 +218,87 @@ pub struct PostgrestErrorResponse {
 	pub details: Option<serde_json::Value>,
 	pub code: Option<String>,
 	pub message: Option<String>,
+	/// HTTP status of the response the error came with
+	#[serde(skip)]
+	pub status: Option<StatusCode>,
+}
+
+impl std::fmt::Display for PostgrestErrorResponse {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}", self.message.as_deref().unwrap_or("PostgREST error"))?;
+		if let Some(code) = &self.code {
+			write!(f, " ({})", code)?;
+		}
+		Ok(())
+	}
+}
+
+/// Parsed `Content-Range` response header e.g. `0-24/3573`, `*/0` or `0-24/*`
+///
+/// https://postgrest.org/en/stable/references/api/pagination_count.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContentRange {
+	/// Zero-based, inclusive indexes of the returned rows, `None` when no rows were returned
+	pub range: Option<(u64, u64)>,
+	/// Total number of rows, only known when a `Count` was requested
+	pub total: Option<u64>,
+}
+
+impl std::str::FromStr for ContentRange {
+	type Err = ();
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let (range, total) = s.trim().split_once('/').ok_or(())?;
+		let range = match range {
+			"*" => None,
+			range => {
+				let (from, to) = range.split_once('-').ok_or(())?;
+				let (from, to): (u64, u64) = (from.parse().map_err(|_| ())?, to.parse().map_err(|_| ())?);
+				if from > to {
+					return Err(());
+				}
+				Some((from, to))
+			}
+		};
+		let total = match total {
+			"*" => None,
+			total => Some(total.parse().map_err(|_| ())?),
+		};
+		Ok(ContentRange { range, total })
+	}
+}
+
+/// Everything PostgREST responded with, returned by `exec_response` and `exec_blocking_response`
+#[derive(Debug)]
+pub struct PostgrestResponse<T> {
+	pub data: T,
+	pub status: StatusCode,
+	pub headers: HeaderMap,
+	/// Total number of rows from `Content-Range`, only present when a `Count` was requested
+	pub count: Option<u64>,
+	pub range: Option<ContentRange>,
+	/// The `Preference-Applied` header, i.e. which of the `Prefer` preferences PostgREST honoured
+	pub preference_applied: Option<String>,
+}
+
+pub(crate) fn content_range(headers: &HeaderMap) -> Option<ContentRange> {
+	headers.get(CONTENT_RANGE)?.to_str().ok()?.parse().ok()
+}
+
+/// Number of rows PostgREST reports in `Content-Range`, only the total when `total_required` and otherwise the size of
+/// the returned range if it didn't count them
+fn affected_rows(headers: &HeaderMap, total_required: bool) -> Result<u64, PostgrestError> {
+	let value = headers
+		.get(CONTENT_RANGE)
+		.ok_or_else(|| PostgrestError::InvalidContentRange(String::new()))?;
+	let invalid = || PostgrestError::InvalidContentRange(String::from_utf8_lossy(value.as_bytes()).into_owned());
+	let range: ContentRange = value.to_str().ok().and_then(|range| range.parse().ok()).ok_or_else(invalid)?;
+	match (range.total, range.range) {
+		(Some(total), _) => Ok(total),
+		(None, _) if total_required => Err(invalid()),
+		(None, Some((from, to))) => to.checked_sub(from).and_then(|rows| rows.checked_add(1)).ok_or_else(invalid),
+		(None, None) => Ok(0),
+	}
 }
 
 pub struct PostgrestHandler<T> {
@@ -24,11 +307,13 @@ pub struct PostgrestHandler<T> {
 	pub method: reqwest::Method,
 	pub query_type: PostgrestQuery,
 	pub body: Option<T>,
+	/// Decode the returned rows of a mutation instead of counting them
+	pub representation: bool,
+	pub transport: Arc<dyn Transport>,
 }
 
 pub const USER_AGENT: &str = concat!("postgrest-query", env!("CARGO_PKG_VERSION"));
 
-
 impl<T> PostgrestHandler<T>
 where
 	T: Serialize + DeserializeOwned,
@@ -40,6 +325,8 @@ where
 			method,
 			body,
 			query_type,
+			representation: false,
+			transport: Arc::new(HttpClient::default()),
 		}
 	}
 
@@ -47,109 +334,107 @@ where
 	where
 		O: Serialize + DeserializeOwned,
 	{
-		let client = BlockingClient::new();
-		let mut headers = self.headers.unwrap_or_else(HeaderMap::new);
-		headers.insert(REQWEST_USER_AGENT, USER_AGENT.parse().unwrap());
-		let mut req_builder = client.request(self.method, self.url).headers(headers);
-
-		if let Some(body) = &self.body {
-			req_builder = req_builder.json(&body);
-		}
+		self.exec_blocking_response().map(|res| res.data)
+	}
 
-		let res = req_builder.send();
-
-		match res {
-			Ok(res) => {
-				if res.status().is_success() {
-					// Before we try and deserialize the response, check to make sure this isnt a mutation query
-					if self.query_type == PostgrestQuery::Create {
-						// Calc + return num of inputted rows
-						let json_value: Value = serde_json::json!(1);
-						let result: Result<O, _> = serde_json::from_value(json_value);
-						return Ok(result.unwrap());
-					}
-
-					if self.query_type == PostgrestQuery::CreateMany {
-						// We can safely unwrap here because we know we have a body due to `query_type`
-						let body = self.body.unwrap();
-						let json_body: Value = serde_json::json!(body);
-						let result: Result<Vec<Value>, _> = serde_json::from_value(json_body);
-
-						let num_rows = result.unwrap().len();
-						let json_value: Value = serde_json::json!(num_rows);
-						let result: Result<O, _> = serde_json::from_value(json_value);
-						return Ok(result.unwrap());
-					}
-					match res.json::<O>() {
-						Ok(res) => {
-							return Ok(res);
-						}
-						Err(e) => {
-							return Err(PostgrestError::ReqwestError(e));
-						}
-					}
-				}
-				let err = res.json::<PostgrestErrorResponse>().unwrap();
-				return Err(PostgrestError::PostgrestErrorResponse(err));
-			}
-			Err(e) => {
-				return Err(PostgrestError::ReqwestError(e));
-			}
-		}
+	/// Same as `exec_blocking` but returns the whole `PostgrestResponse` (status, headers, count and range)
+	pub fn exec_blocking_response<O>(self) -> Result<PostgrestResponse<O>, PostgrestError>
+	where
+		O: Serialize + DeserializeOwned,
+	{
+		let res = self.transport.send_blocking(self.request()?)?;
+		self.into_response(res.status, res.headers, &res.body)
 	}
 
 	pub async fn exec<O>(self) -> Result<O, PostgrestError>
 	where
 		O: Serialize + DeserializeOwned,
 	{
-		let client = Client::new();
-		let mut headers = self.headers.unwrap_or_else(HeaderMap::new);
-		headers.insert(REQWEST_USER_AGENT, USER_AGENT.parse().unwrap());
-		let mut req_builder = client.request(self.method, self.url).headers(headers);
+		self.exec_response().await.map(|res| res.data)
+	}
 
-		if let Some(body) = &self.body {
-			req_builder = req_builder.json(body);
-		}
+	/// Same as `exec` but returns the whole `PostgrestResponse` (status, headers, count and range)
+	pub async fn exec_response<O>(self) -> Result<PostgrestResponse<O>, PostgrestError>
+	where
+		O: Serialize + DeserializeOwned,
+	{
+		let res = self.transport.send(self.request()?).await?;
+		self.into_response(res.status, res.headers, &res.body)
+	}
 
-		let res = req_builder.send().await;
-
-		match res {
-			Ok(res) => {
-				if res.status().is_success() {
-					// Before we try and deserialize the response, check to make sure this isnt a mutation query
-					if self.query_type == PostgrestQuery::Create {
-						// Calc + return num of inputted rows
-						let json_value: Value = serde_json::json!(1);
-						let result: Result<O, _> = serde_json::from_value(json_value);
-						return Ok(result.unwrap());
-					}
-
-					if self.query_type == PostgrestQuery::CreateMany {
-						// We can safely unwrap here because we know we have a body due to `query_type`
-						let body = self.body.unwrap();
-						let json_body: Value = serde_json::json!(body);
-						let result: Result<Vec<Value>, _> = serde_json::from_value(json_body);
-
-						let num_rows = result.unwrap().len();
-						let json_value: Value = serde_json::json!(num_rows);
-						let result: Result<O, _> = serde_json::from_value(json_value);
-						return Ok(result.unwrap());
-					}
-					match res.json::<O>().await {
-						Ok(res) => {
-							return Ok(res);
-						}
-						Err(e) => {
-							return Err(PostgrestError::ReqwestError(e));
-						}
-					}
+	/// Build the request handed to the `Transport`, the body is encoded as JSON
+	fn request(&self) -> Result<TransportRequest, PostgrestError> {
+		let mut headers = self.headers.clone().unwrap_or_default();
+		headers.insert(REQWEST_USER_AGENT, HeaderValue::from_static(USER_AGENT));
+
+		let body = match &self.body {
+			Some(body) => {
+				headers.entry(CONTENT_TYPE).or_insert(HeaderValue::from_static("application/json"));
+				Some(serde_json::to_vec(body).map_err(PostgrestError::SerdeError)?)
+			}
+			None => None,
+		};
+
+		Ok(TransportRequest {
+			method: self.method.clone(),
+			url: self.url.clone(),
+			headers,
+			body,
+		})
+	}
+
+	/// Shared by `exec` and `exec_blocking` once the `Transport` responded
+	fn into_response<O>(self, status: StatusCode, headers: HeaderMap, body: &[u8]) -> Result<PostgrestResponse<O>, PostgrestError>
+	where
+		O: Serialize + DeserializeOwned,
+	{
+		if !status.is_success() {
+			// Every field is optional, so only a body with a `code` is PostgREST's rather than e.g. a proxy's
+			return Err(match serde_json::from_slice::<PostgrestErrorResponse>(body) {
+				Ok(mut err) if err.code.is_some() => {
+					err.status = Some(status);
+					err.into()
 				}
-				let err = res.json::<PostgrestErrorResponse>().await.unwrap();
-				return Err(PostgrestError::PostgrestErrorResponse(err));
+				_ => PostgrestError::NonJsonErrorBody {
+					status,
+					body: String::from_utf8_lossy(body).into_owned(),
+				},
+			});
+		}
+
+		let range = content_range(&headers);
+
+		// Before we try and deserialize the response, check to make sure this isnt a mutation query
+		let data = match self.query_type {
+			PostgrestQuery::Count => serde_json::from_value(serde_json::json!(affected_rows(&headers, true)?)),
+			PostgrestQuery::Exists => serde_json::from_value(serde_json::json!(affected_rows(&headers, false)?)),
+			PostgrestQuery::Create
+			| PostgrestQuery::CreateMany
+			| PostgrestQuery::Update
+			| PostgrestQuery::UpdateMany
+			| PostgrestQuery::Upsert
+			| PostgrestQuery::Delete
+			| PostgrestQuery::DeleteMany
+				if !self.representation =>
+			{
+				// The builders always ask for a count, so a mutation without a total wasn't counted
+				serde_json::from_value(serde_json::json!(affected_rows(&headers, true)?))
 			}
-			Err(e) => {
-				return Err(PostgrestError::ReqwestError(e));
+			// Fetched with `limit=1`, so `null` (i.e. `None`) when no row matched
+			PostgrestQuery::FindFirst => {
+				serde_json::from_slice::<Vec<Value>>(body).and_then(|rows| serde_json::from_value(rows.into_iter().next().unwrap_or(Value::Null)))
 			}
+			_ => serde_json::from_slice(body),
 		}
+		.map_err(PostgrestError::SerdeError)?;
+
+		Ok(PostgrestResponse {
+			data,
+			status,
+			count: range.as_ref().and_then(|range| range.total),
+			range,
+			preference_applied: headers.get("Preference-Applied").and_then(|value| value.to_str().ok()).map(String::from),
+			headers,
+		})
 	}
 }
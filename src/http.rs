@@ -0,0 +1,109 @@
+use crate::{
+	handler::PostgrestError,
+	transport::{Transport, TransportFuture, TransportRequest, TransportResponse},
+};
+use reqwest::{blocking::Client as BlockingClient, Client};
+use std::{
+	sync::{Arc, OnceLock},
+	time::Duration,
+};
+
+/// Connection pool settings for the HTTP clients owned by `PostgrestClient`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PoolConfig {
+	/// Maximum idle connections kept open per host, reqwest's default (unlimited) when `None`
+	pub max_idle_per_host: Option<usize>,
+	/// How long an idle connection is kept before being closed, reqwest's default (90 seconds) when `None`
+	pub idle_timeout: Option<Duration>,
+}
+
+/// The default `Transport`, long-lived reqwest clients shared by every query built from the same `PostgrestClient`
+///
+/// Cloning is cheap and keeps using the same connection pool, TLS sessions and keep-alive connections. Each client
+/// is only built the first time it's needed, the blocking one on the first `exec_blocking`.
+#[derive(Debug, Clone, Default)]
+pub struct HttpClient {
+	pub pool: PoolConfig,
+	client: Arc<OnceLock<Client>>,
+	blocking_client: Arc<OnceLock<BlockingClient>>,
+}
+
+impl HttpClient {
+	pub fn new(pool: PoolConfig) -> Self {
+		HttpClient { pool, ..Default::default() }
+	}
+
+	pub fn client(&self) -> Result<&Client, reqwest::Error> {
+		if let Some(client) = self.client.get() {
+			return Ok(client);
+		}
+		let mut builder = Client::builder();
+		if let Some(max_idle_per_host) = self.pool.max_idle_per_host {
+			builder = builder.pool_max_idle_per_host(max_idle_per_host);
+		}
+		if let Some(idle_timeout) = self.pool.idle_timeout {
+			builder = builder.pool_idle_timeout(idle_timeout);
+		}
+		let client = builder.build()?;
+		Ok(self.client.get_or_init(|| client))
+	}
+
+	pub fn blocking_client(&self) -> Result<&BlockingClient, reqwest::Error> {
+		if let Some(client) = self.blocking_client.get() {
+			return Ok(client);
+		}
+		let mut builder = BlockingClient::builder();
+		if let Some(max_idle_per_host) = self.pool.max_idle_per_host {
+			builder = builder.pool_max_idle_per_host(max_idle_per_host);
+		}
+		if let Some(idle_timeout) = self.pool.idle_timeout {
+			builder = builder.pool_idle_timeout(idle_timeout);
+		}
+		let client = builder.build()?;
+		Ok(self.blocking_client.get_or_init(|| client))
+	}
+}
+
+impl Transport for HttpClient {
+	fn send(&self, request: TransportRequest) -> TransportFuture<'_> {
+		Box::pin(async move {
+			let client = self.client().map_err(PostgrestError::ReqwestError)?;
+			let mut req_builder = client.request(request.method, request.url).headers(request.headers);
+
+			if let Some(body) = request.body {
+				req_builder = req_builder.body(body);
+			}
+
+			let res = req_builder.send().await.map_err(PostgrestError::ReqwestError)?;
+			let status = res.status();
+			let headers = res.headers().clone();
+			let body = res.bytes().await.map_err(PostgrestError::ReqwestError)?;
+
+			Ok(TransportResponse {
+				status,
+				headers,
+				body: body.to_vec(),
+			})
+		})
+	}
+
+	fn send_blocking(&self, request: TransportRequest) -> Result<TransportResponse, PostgrestError> {
+		let client = self.blocking_client().map_err(PostgrestError::ReqwestError)?;
+		let mut req_builder = client.request(request.method, request.url).headers(request.headers);
+
+		if let Some(body) = request.body {
+			req_builder = req_builder.body(body);
+		}
+
+		let res = req_builder.send().map_err(PostgrestError::ReqwestError)?;
+		let status = res.status();
+		let headers = res.headers().clone();
+		let body = res.bytes().map_err(PostgrestError::ReqwestError)?;
+
+		Ok(TransportResponse {
+			status,
+			headers,
+			body: body.to_vec(),
+		})
+	}
+}
@@ -1,4 +1,13 @@
-pub(crate) mod builder;
+pub mod builder;
 pub mod client;
-pub(crate) mod filter;
-pub(crate) mod handler;
+pub mod condition;
+pub mod filter;
+pub mod handler;
+pub mod http;
+pub mod prefer;
+pub mod select;
+pub mod state;
+pub mod transport;
+#[cfg(unix)]
+pub mod unix;
+pub mod value;
@@ -0,0 +1,176 @@
+use crate::builder::{Count, Return};
+use std::fmt;
+
+/// How an upsert resolves rows that conflict with an existing primary key or `on_conflict` columns
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+	MergeDuplicates,
+	IgnoreDuplicates,
+}
+
+/// What columns missing from the payload of an insert/update are set to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Missing {
+	Default,
+	Null,
+}
+
+/// Whether PostgREST ignores (`lenient`) or rejects (`strict`) preferences it can't honour
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Handling {
+	Lenient,
+	Strict,
+}
+
+/// Commit or roll back the transaction once the request finished, requires `db-tx-end` to allow overrides
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tx {
+	Commit,
+	Rollback,
+}
+
+/// How the body of an RPC call is passed to the function
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Params {
+	/// Pass the whole JSON body as a single `json`/`jsonb` argument
+	SingleObject,
+}
+
+/// A typed `Prefer` header
+///
+/// Preferences from the headers given to `PostgrestClient` are parsed into this type and merged with the ones
+/// the builder methods (and `PostgrestFilter::prefer`) set, so neither side clobbers the other.
+///
+/// https://postgrest.org/en/stable/references/api/preferences.html
+///
+/// # Example
+/// ```ignore
+/// let prefer = Preferences { tx: Some(Tx::Rollback), timezone: Some(String::from("UTC")), ..Default::default() };
+/// db.from("users").create(user, None, None).prefer(prefer).exec().await;
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Preferences {
+	pub returning: Option<Return>,
+	pub count: Option<Count>,
+	pub resolution: Option<Resolution>,
+	pub missing: Option<Missing>,
+	pub handling: Option<Handling>,
+	pub tx: Option<Tx>,
+	pub timezone: Option<String>,
+	pub max_affected: Option<u64>,
+	pub params: Option<Params>,
+	/// Any preference this crate doesn't model, sent as-is
+	pub other: Vec<String>,
+}
+
+impl Preferences {
+	/// Parse a `Prefer` header value, unknown preferences end up in `other` and a repeated preference keeps its last value
+	pub fn parse(header: &str) -> Self {
+		let mut prefer = Preferences::default();
+		for pref in header.split(',').map(str::trim).filter(|pref| !pref.is_empty()) {
+			let (key, value) = pref.split_once('=').unwrap_or((pref, ""));
+			let known = match (key.trim(), value.trim()) {
+				("return", "minimal") => set(&mut prefer.returning, Return::Minimal),
+				("return", "headers-only") => set(&mut prefer.returning, Return::HeadersOnly),
+				("return", "representation") => set(&mut prefer.returning, Return::Representation),
+				("count", "exact") => set(&mut prefer.count, Count::Exact),
+				("count", "planned") => set(&mut prefer.count, Count::Planned),
+				("count", "estimated") => set(&mut prefer.count, Count::Estimated),
+				("resolution", "merge-duplicates") => set(&mut prefer.resolution, Resolution::MergeDuplicates),
+				("resolution", "ignore-duplicates") => set(&mut prefer.resolution, Resolution::IgnoreDuplicates),
+				("missing", "default") => set(&mut prefer.missing, Missing::Default),
+				("missing", "null") => set(&mut prefer.missing, Missing::Null),
+				("handling", "lenient") => set(&mut prefer.handling, Handling::Lenient),
+				("handling", "strict") => set(&mut prefer.handling, Handling::Strict),
+				("tx", "commit") => set(&mut prefer.tx, Tx::Commit),
+				("tx", "rollback") => set(&mut prefer.tx, Tx::Rollback),
+				("timezone", timezone) if !timezone.is_empty() => set(&mut prefer.timezone, timezone.to_string()),
+				("max-affected", max) => match max.parse() {
+					Ok(max) => set(&mut prefer.max_affected, max),
+					Err(_) => false,
+				},
+				("params", "single-object") => set(&mut prefer.params, Params::SingleObject),
+				_ => false,
+			};
+			if !known {
+				prefer.other.push(pref.to_string());
+			}
+		}
+		prefer
+	}
+
+	/// Merge `other` into `self`, preferences set in `other` win and unknown preferences are combined
+	pub fn merge(&mut self, other: Preferences) {
+		self.returning = other.returning.or(self.returning);
+		self.count = other.count.or(self.count);
+		self.resolution = other.resolution.or(self.resolution);
+		self.missing = other.missing.or(self.missing);
+		self.handling = other.handling.or(self.handling);
+		self.tx = other.tx.or(self.tx);
+		self.timezone = other.timezone.or(self.timezone.take());
+		self.max_affected = other.max_affected.or(self.max_affected);
+		self.params = other.params.or(self.params);
+		for pref in other.other {
+			if !self.other.contains(&pref) {
+				self.other.push(pref);
+			}
+		}
+	}
+
+	pub fn is_empty(&self) -> bool {
+		*self == Preferences::default()
+	}
+}
+
+/// Set a parsed preference, replacing an earlier value for the same key
+fn set<V>(slot: &mut Option<V>, value: V) -> bool {
+	*slot = Some(value);
+	true
+}
+
+impl fmt::Display for Preferences {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let mut prefs: Vec<String> = Vec::new();
+		if let Some(returning) = &self.returning {
+			prefs.push(format!("return={}", returning));
+		}
+		if let Some(count) = &self.count {
+			prefs.push(format!("count={}", count));
+		}
+		if let Some(resolution) = &self.resolution {
+			prefs.push(String::from(match resolution {
+				Resolution::MergeDuplicates => "resolution=merge-duplicates",
+				Resolution::IgnoreDuplicates => "resolution=ignore-duplicates",
+			}));
+		}
+		if let Some(missing) = &self.missing {
+			prefs.push(String::from(match missing {
+				Missing::Default => "missing=default",
+				Missing::Null => "missing=null",
+			}));
+		}
+		if let Some(handling) = &self.handling {
+			prefs.push(String::from(match handling {
+				Handling::Lenient => "handling=lenient",
+				Handling::Strict => "handling=strict",
+			}));
+		}
+		if let Some(tx) = &self.tx {
+			prefs.push(String::from(match tx {
+				Tx::Commit => "tx=commit",
+				Tx::Rollback => "tx=rollback",
+			}));
+		}
+		if let Some(timezone) = &self.timezone {
+			prefs.push(format!("timezone={}", timezone));
+		}
+		if let Some(max_affected) = &self.max_affected {
+			prefs.push(format!("max-affected={}", max_affected));
+		}
+		if let Some(Params::SingleObject) = &self.params {
+			prefs.push(String::from("params=single-object"));
+		}
+		prefs.extend(self.other.iter().cloned());
+		write!(f, "{}", prefs.join(","))
+	}
+}
@@ -0,0 +1,103 @@
+use std::fmt;
+
+/// A related table embedded into a `select`, resolved by PostgREST through its foreign keys
+///
+/// Many-to-many relationships are embedded through their junction table automatically, `hint` is only needed
+/// when more than one foreign key (or junction table) connects the two tables.
+///
+/// https://postgrest.org/en/stable/references/api/resource_embedding.html
+///
+/// # Example
+/// ```ignore
+/// // select=id,name,orders!customer_fk!inner(id,total,items(*))
+/// let orders = Embed::new("orders").hint("customer_fk").inner().select("id,total").embed(Embed::new("items"));
+/// db.from("customers").find_many::<Customer>().select("id,name").embed(orders).exec().await;
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Embed {
+	pub relation: String,
+	pub alias: Option<String>,
+	pub hint: Option<String>,
+	pub inner: bool,
+	pub columns: String,
+	pub embeds: Vec<Embed>,
+}
+
+impl Embed {
+	pub fn new(relation: &str) -> Self {
+		Embed {
+			relation: relation.to_string(),
+			alias: None,
+			hint: None,
+			inner: false,
+			columns: String::from("*"),
+			embeds: Vec::new(),
+		}
+	}
+
+	/// Rename the embedded resource in the response (`alias:relation(...)`)
+	pub fn alias(mut self, alias: &str) -> Self {
+		self.alias = Some(alias.to_string());
+		self
+	}
+
+	/// Disambiguate the relationship by foreign key, column or junction table name (`relation!hint(...)`)
+	pub fn hint(mut self, hint: &str) -> Self {
+		self.hint = Some(hint.to_string());
+		self
+	}
+
+	/// Only return parent rows that have at least one matching embedded row (`relation!inner(...)`)
+	pub fn inner(mut self) -> Self {
+		self.inner = true;
+		self
+	}
+
+	/// Columns to return from the embedded resource, same syntax as `PostgrestFilter::select`
+	pub fn select(mut self, columns: &str) -> Self {
+		self.columns = clean_columns(columns);
+		self
+	}
+
+	/// Embed another resource inside of this one
+	pub fn embed(mut self, embed: Embed) -> Self {
+		self.embeds.push(embed);
+		self
+	}
+}
+
+impl fmt::Display for Embed {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		if let Some(alias) = &self.alias {
+			write!(f, "{}:", alias)?;
+		}
+		write!(f, "{}", self.relation)?;
+		if let Some(hint) = &self.hint {
+			write!(f, "!{}", hint)?;
+		}
+		if self.inner {
+			write!(f, "!inner")?;
+		}
+
+		let mut columns: Vec<String> = Vec::new();
+		if !self.columns.is_empty() {
+			columns.push(self.columns.clone());
+		}
+		columns.extend(self.embeds.iter().map(|embed| embed.to_string()));
+		write!(f, "({})", columns.join(","))
+	}
+}
+
+/// Strip whitespace from a select list, leaving quoted identifiers (`"first name"`) untouched
+pub(crate) fn clean_columns(columns: &str) -> String {
+	let mut quoted = false;
+	columns
+		.chars()
+		.filter(|c| {
+			if *c == '"' {
+				quoted = !quoted;
+			}
+			quoted || !c.is_whitespace()
+		})
+		.collect()
+}
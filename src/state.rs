@@ -0,0 +1,116 @@
+//! Marker types for the state parameter of `PostgrestFilter`, deciding which methods a query has
+//!
+//! - `Read` (`find_many`, `find_first`): filters, ordering, pagination, embedded modifiers, `count` and `exists`
+//! - `Single` (`find_unique`): filters only, the response is a single row
+//! - `Insert` (`create`, `create_many`) and `Upsert` (`upsert`, which also has `on_conflict`): no filters
+//! - `Unfiltered` (`update`, `delete`, ...): filters but no `exec` until one is added or `all_rows` is called,
+//!   after which the query is `Filtered`
+//! - `SingleUnfiltered` (`update_one`, `delete_one`): like `Unfiltered` then `SingleFiltered`, but the response is
+//!   the single affected row, so there's no `returning`, `prefer_return` or `max_affected`
+//!
+//! ```compile_fail
+//! # use postgrest_query::client::PostgrestClient;
+//! # #[derive(serde::Serialize, serde::Deserialize)]
+//! # struct User { id: i32 }
+//! let db = PostgrestClient::new("http://localhost:3000".to_string(), None).unwrap();
+//! // A delete has to be filtered (or opt in with `all_rows`) before it can be executed
+//! db.from("users").delete::<User>(None).exec_blocking();
+//! ```
+//!
+//! ```compile_fail
+//! # use postgrest_query::{client::PostgrestClient, filter::Order};
+//! # #[derive(serde::Serialize, serde::Deserialize)]
+//! # struct User { id: i32 }
+//! let db = PostgrestClient::new("http://localhost:3000".to_string(), None).unwrap();
+//! // Only reads can be ordered
+//! db.from("users").update(User { id: 1 }, None).eq("id", 1).order(Order::asc("id"));
+//! ```
+//!
+//! ```compile_fail
+//! # use postgrest_query::client::PostgrestClient;
+//! # #[derive(serde::Serialize, serde::Deserialize)]
+//! # struct User { id: i32 }
+//! let db = PostgrestClient::new("http://localhost:3000".to_string(), None).unwrap();
+//! // `on_conflict` only exists on upserts
+//! db.from("users").create(User { id: 1 }, None, None).on_conflict("id");
+//! ```
+//!
+//! ```compile_fail
+//! # use postgrest_query::client::PostgrestClient;
+//! # #[derive(serde::Serialize, serde::Deserialize)]
+//! # struct User { id: i32 }
+//! let db = PostgrestClient::new("http://localhost:3000".to_string(), None).unwrap();
+//! // `update_one` already resolves to the updated row
+//! db.from("users").update_one(User { id: 1 }).eq("id", 1).returning::<User>();
+//! ```
+
+/// A SELECT returning any number of rows
+pub struct Read;
+
+/// A SELECT returning exactly one row
+pub struct Single;
+
+/// An INSERT
+pub struct Insert;
+
+/// An INSERT that merges or ignores duplicates
+pub struct Upsert;
+
+/// An UPDATE or DELETE without filters yet, it can't be executed
+pub struct Unfiltered;
+
+/// An UPDATE or DELETE with at least one filter, or one explicitly affecting all rows
+pub struct Filtered;
+
+/// An UPDATE or DELETE of exactly one row without filters yet, it can't be executed
+pub struct SingleUnfiltered;
+
+/// An UPDATE or DELETE of exactly one row with at least one filter
+pub struct SingleFiltered;
+
+/// States that can be executed
+pub trait Executable {}
+
+impl Executable for Read {}
+impl Executable for Single {}
+impl Executable for Insert {}
+impl Executable for Upsert {}
+impl Executable for Filtered {}
+impl Executable for SingleFiltered {}
+
+/// States that accept filters, `Filtered` is the state once one was added
+pub trait Filterable {
+	type Filtered;
+}
+
+impl Filterable for Read {
+	type Filtered = Read;
+}
+
+impl Filterable for Single {
+	type Filtered = Single;
+}
+
+impl Filterable for Unfiltered {
+	type Filtered = Filtered;
+}
+
+impl Filterable for Filtered {
+	type Filtered = Filtered;
+}
+
+impl Filterable for SingleUnfiltered {
+	type Filtered = SingleFiltered;
+}
+
+impl Filterable for SingleFiltered {
+	type Filtered = SingleFiltered;
+}
+
+/// States of queries that write rows, which can return them with `returning`
+pub trait Mutation {}
+
+impl Mutation for Insert {}
+impl Mutation for Upsert {}
+impl Mutation for Unfiltered {}
+impl Mutation for Filtered {}
@@ -0,0 +1,51 @@
+use crate::handler::PostgrestError;
+use reqwest::{header::HeaderMap, Method, StatusCode};
+use std::{fmt, future::Future, pin::Pin};
+use url::Url;
+
+/// A fully built request, ready to be sent by a `Transport`
+#[derive(Debug, Clone)]
+pub struct TransportRequest {
+	pub method: Method,
+	pub url: Url,
+	pub headers: HeaderMap,
+	/// JSON encoded body, `None` for requests without one (e.g. `GET` or `DELETE`)
+	pub body: Option<Vec<u8>>,
+}
+
+/// The raw response a `Transport` received, parsed by the crate into a `PostgrestResponse`
+#[derive(Debug, Clone)]
+pub struct TransportResponse {
+	pub status: StatusCode,
+	pub headers: HeaderMap,
+	pub body: Vec<u8>,
+}
+
+pub type TransportFuture<'a> = Pin<Box<dyn Future<Output = Result<TransportResponse, PostgrestError>> + Send + 'a>>;
+
+/// Sends requests to PostgREST, `HttpClient` (reqwest) is used unless another one is given to `PostgrestClient::transport`
+///
+/// Implement this to use another HTTP client (hyper, ureq, ...) or an in-process test double.
+///
+/// # Example
+/// ```ignore
+/// #[derive(Debug)]
+/// struct Empty;
+///
+/// impl Transport for Empty {
+///     fn send(&self, request: TransportRequest) -> TransportFuture<'_> {
+///         Box::pin(async move { self.send_blocking(request) })
+///     }
+///
+///     fn send_blocking(&self, _request: TransportRequest) -> Result<TransportResponse, PostgrestError> {
+///         Ok(TransportResponse { status: StatusCode::OK, headers: HeaderMap::new(), body: b"[]".to_vec() })
+///     }
+/// }
+///
+/// let db = PostgrestClient::new(URL.to_string(), None)?.transport(Empty);
+/// ```
+pub trait Transport: fmt::Debug + Send + Sync {
+	fn send(&self, request: TransportRequest) -> TransportFuture<'_>;
+
+	fn send_blocking(&self, request: TransportRequest) -> Result<TransportResponse, PostgrestError>;
+}
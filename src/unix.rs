@@ -0,0 +1,161 @@
+use crate::{
+	handler::PostgrestError,
+	transport::{Transport, TransportFuture, TransportRequest, TransportResponse},
+};
+use reqwest::{
+	header::{HeaderMap, HeaderName, HeaderValue, CONTENT_LENGTH, TRANSFER_ENCODING},
+	Method, StatusCode,
+};
+use std::{
+	io::{self, Read, Write},
+	path::{Path, PathBuf},
+};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// A `Transport` speaking HTTP/1.1 over a Unix domain socket, for a PostgREST started with `server-unix-socket`
+///
+/// The client's URL only supplies the path, query and `Host` header, e.g. `http://localhost`. Every request opens
+/// its own connection, which on a local socket costs far less than a TCP round trip.
+///
+/// # Example
+/// ```ignore
+/// let db = PostgrestClient::new("http://localhost".to_string(), None)?.transport(UnixSocketTransport::new("/tmp/pgrst.sock"));
+/// let users = db.from("users").find_many::<User>().exec().await;
+/// ```
+#[derive(Debug, Clone)]
+pub struct UnixSocketTransport {
+	pub path: PathBuf,
+}
+
+impl UnixSocketTransport {
+	pub fn new(path: impl AsRef<Path>) -> Self {
+		UnixSocketTransport {
+			path: path.as_ref().to_path_buf(),
+		}
+	}
+}
+
+impl Transport for UnixSocketTransport {
+	fn send(&self, request: TransportRequest) -> TransportFuture<'_> {
+		Box::pin(async move {
+			let mut stream = tokio::net::UnixStream::connect(&self.path).await.map_err(PostgrestError::IoError)?;
+			stream.write_all(&encode_request(&request)).await.map_err(PostgrestError::IoError)?;
+
+			let mut response = Vec::new();
+			stream.read_to_end(&mut response).await.map_err(PostgrestError::IoError)?;
+
+			decode_response(&request.method, &response).map_err(PostgrestError::IoError)
+		})
+	}
+
+	fn send_blocking(&self, request: TransportRequest) -> Result<TransportResponse, PostgrestError> {
+		let mut stream = std::os::unix::net::UnixStream::connect(&self.path).map_err(PostgrestError::IoError)?;
+		stream.write_all(&encode_request(&request)).map_err(PostgrestError::IoError)?;
+
+		let mut response = Vec::new();
+		stream.read_to_end(&mut response).map_err(PostgrestError::IoError)?;
+
+		decode_response(&request.method, &response).map_err(PostgrestError::IoError)
+	}
+}
+
+/// Serialize the request, asking the server to close the connection so the response ends at EOF
+fn encode_request(request: &TransportRequest) -> Vec<u8> {
+	let mut target = request.url.path().to_string();
+	if let Some(query) = request.url.query() {
+		target.push('?');
+		target.push_str(query);
+	}
+	let host = match (request.url.host_str(), request.url.port()) {
+		(Some(host), Some(port)) => format!("{}:{}", host, port),
+		(Some(host), None) => host.to_string(),
+		_ => "localhost".to_string(),
+	};
+
+	let mut buf = format!("{} {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n", request.method, target, host).into_bytes();
+	for (name, value) in &request.headers {
+		if name == CONTENT_LENGTH || name == TRANSFER_ENCODING {
+			continue;
+		}
+		buf.extend_from_slice(name.as_str().as_bytes());
+		buf.extend_from_slice(b": ");
+		buf.extend_from_slice(value.as_bytes());
+		buf.extend_from_slice(b"\r\n");
+	}
+	if let Some(body) = &request.body {
+		buf.extend_from_slice(format!("Content-Length: {}\r\n\r\n", body.len()).as_bytes());
+		buf.extend_from_slice(body);
+	} else {
+		buf.extend_from_slice(b"\r\n");
+	}
+	buf
+}
+
+fn invalid(message: &str) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn decode_response(method: &Method, response: &[u8]) -> Result<TransportResponse, io::Error> {
+	let end = response
+		.windows(4)
+		.position(|window| window == b"\r\n\r\n")
+		.ok_or_else(|| invalid("incomplete HTTP response head"))?;
+	let head = std::str::from_utf8(&response[..end]).map_err(|_| invalid("HTTP response head is not UTF-8"))?;
+	let rest = &response[end + 4..];
+
+	let mut lines = head.split("\r\n");
+	let status = lines
+		.next()
+		.and_then(|line| line.split(' ').nth(1))
+		.and_then(|code| StatusCode::from_bytes(code.as_bytes()).ok())
+		.ok_or_else(|| invalid("invalid HTTP status line"))?;
+
+	let mut headers = HeaderMap::new();
+	for line in lines {
+		let (name, value) = line.split_once(':').ok_or_else(|| invalid("invalid HTTP header"))?;
+		let name = HeaderName::from_bytes(name.trim().as_bytes()).map_err(|_| invalid("invalid HTTP header name"))?;
+		let value = HeaderValue::from_str(value.trim()).map_err(|_| invalid("invalid HTTP header value"))?;
+		headers.append(name, value);
+	}
+
+	let chunked = headers
+		.get(TRANSFER_ENCODING)
+		.and_then(|value| value.to_str().ok())
+		.is_some_and(|value| value.eq_ignore_ascii_case("chunked"));
+	let content_length = headers.get(CONTENT_LENGTH).and_then(|value| value.to_str().ok()?.parse::<usize>().ok());
+
+	let body = if *method == Method::HEAD || status == StatusCode::NO_CONTENT || status == StatusCode::NOT_MODIFIED {
+		Vec::new()
+	} else if chunked {
+		decode_chunked(rest)?
+	} else if let Some(length) = content_length {
+		rest.get(..length)
+			.ok_or_else(|| invalid("HTTP response body is shorter than its Content-Length"))?
+			.to_vec()
+	} else {
+		rest.to_vec()
+	};
+
+	Ok(TransportResponse { status, headers, body })
+}
+
+/// Decode a `Transfer-Encoding: chunked` body, ignoring chunk extensions and trailers
+fn decode_chunked(mut rest: &[u8]) -> Result<Vec<u8>, io::Error> {
+	let mut body = Vec::new();
+	loop {
+		let end = rest
+			.windows(2)
+			.position(|window| window == b"\r\n")
+			.ok_or_else(|| invalid("incomplete chunk size"))?;
+		let size = std::str::from_utf8(&rest[..end])
+			.ok()
+			.and_then(|line| usize::from_str_radix(line.split(';').next()?.trim(), 16).ok())
+			.ok_or_else(|| invalid("invalid chunk size"))?;
+		rest = &rest[end + 2..];
+		if size == 0 {
+			return Ok(body);
+		}
+		body.extend_from_slice(rest.get(..size).ok_or_else(|| invalid("incomplete chunk"))?);
+		rest = rest.get(size + 2..).ok_or_else(|| invalid("incomplete chunk"))?;
+	}
+}
@@ -0,0 +1,230 @@
+use crate::filter::FilterType;
+use std::ops::{Bound, RangeBounds};
+
+/// The right-hand side of a filter, quoted and escaped by the crate according to PostgREST's grammar
+///
+/// Strings, numbers and booleans convert into a `Scalar` and a `Vec` into a `List`. The operator decides how a value
+/// is written, so a `Vec` becomes `(...)` for `in` and `{...}` for `cs`. Because every element is quoted where
+/// needed, user-supplied values can never add filters or elements, or break out of a list or group.
+///
+/// # Example
+/// ```ignore
+/// db.from("users")
+///     .find_many::<User>()
+///     .in_filter("name", vec!["O'Brien, Pat", "(unknown)"])
+///     .cs("tags", FilterValue::array(vec!["rust", "c,d"]))
+///     .ov("period", FilterValue::range("2017-01-01".."2017-03-31"))
+///     .exec()
+///     .await;
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FilterValue {
+	/// A single value e.g. `eq.hello`
+	Scalar(String),
+	/// A list of values for `in` e.g. `in.(1,2,"a,b")`
+	List(Vec<String>),
+	/// A Postgres array literal for `cs`, `cd` and `ov` e.g. `{rust,"c,d"}`
+	Array(Vec<String>),
+	/// A Postgres range literal for the range operators e.g. `[1,10)`
+	Range {
+		lower: Option<String>,
+		upper: Option<String>,
+		lower_inclusive: bool,
+		upper_inclusive: bool,
+	},
+}
+
+impl FilterValue {
+	pub fn list<I, V>(values: I) -> Self
+	where
+		I: IntoIterator<Item = V>,
+		V: ToString,
+	{
+		FilterValue::List(values.into_iter().map(|value| value.to_string()).collect())
+	}
+
+	pub fn array<I, V>(values: I) -> Self
+	where
+		I: IntoIterator<Item = V>,
+		V: ToString,
+	{
+		FilterValue::Array(values.into_iter().map(|value| value.to_string()).collect())
+	}
+
+	/// Build a range literal from any Rust range, `1..10` becomes `[1,10)` and `..=5` becomes `(,5]`
+	pub fn range<R, V>(range: R) -> Self
+	where
+		R: RangeBounds<V>,
+		V: ToString,
+	{
+		let (lower, lower_inclusive) = match range.start_bound() {
+			Bound::Included(value) => (Some(value.to_string()), true),
+			Bound::Excluded(value) => (Some(value.to_string()), false),
+			Bound::Unbounded => (None, false),
+		};
+		let (upper, upper_inclusive) = match range.end_bound() {
+			Bound::Included(value) => (Some(value.to_string()), true),
+			Bound::Excluded(value) => (Some(value.to_string()), false),
+			Bound::Unbounded => (None, false),
+		};
+		FilterValue::Range {
+			lower,
+			upper,
+			lower_inclusive,
+			upper_inclusive,
+		}
+	}
+
+	/// Convert a JSON value, arrays become an array literal and objects are compared as JSON text
+	pub(crate) fn from_json(value: &serde_json::Value) -> Self {
+		match value {
+			serde_json::Value::String(value) => FilterValue::Scalar(value.clone()),
+			serde_json::Value::Array(values) => FilterValue::Array(
+				values
+					.iter()
+					.map(|value| match value {
+						serde_json::Value::String(value) => value.clone(),
+						value => value.to_string(),
+					})
+					.collect(),
+			),
+			value => FilterValue::Scalar(value.to_string()),
+		}
+	}
+
+	/// Render the operand for `filter_type`, `nested` values sit inside a logical operator like `or=(...)`
+	///
+	/// The brackets come from the operator rather than the variant: `in` always gets a list, `cs`, `cd` and `ov` an
+	/// array literal (unless given a `Range`) and the range operators a range literal. A lone `Scalar` for any of these
+	/// becomes a single element (a single point for ranges), so it can't add elements of its own.
+	pub(crate) fn render(&self, filter_type: &FilterType, nested: bool) -> String {
+		let composite = match (filter_type, self) {
+			// Lists keep their parentheses inside logical operators
+			(FilterType::In, FilterValue::Scalar(value)) => return list(std::slice::from_ref(value)),
+			(FilterType::In, FilterValue::List(values) | FilterValue::Array(values)) => return list(values),
+			(FilterType::In, FilterValue::Range { .. }) => return list(&[self.range_literal()]),
+			(FilterType::Cs | FilterType::Cd | FilterType::Ov, FilterValue::Range { .. }) => self.range_literal(),
+			(FilterType::Cs | FilterType::Cd | FilterType::Ov, FilterValue::Scalar(value)) => array(std::slice::from_ref(value)),
+			(FilterType::Cs | FilterType::Cd | FilterType::Ov, FilterValue::List(values) | FilterValue::Array(values)) => array(values),
+			(FilterType::Sl | FilterType::Sr | FilterType::Nxl | FilterType::Nxr | FilterType::Adj, _) => self.range_literal(),
+			(_, FilterValue::Scalar(value)) if nested => return quote(value, LIST_RESERVED),
+			// At the top level everything after `op.` is the value, so no quoting is required
+			(_, FilterValue::Scalar(value)) => return value.clone(),
+			(_, FilterValue::List(values)) => return list(values),
+			(_, FilterValue::Array(values)) => array(values),
+			(_, FilterValue::Range { .. }) => self.range_literal(),
+		};
+		// Arrays and ranges contain commas, so inside a logical operator they're quoted as a whole
+		if nested {
+			quote(&composite, LIST_RESERVED)
+		} else {
+			composite
+		}
+	}
+
+	/// A range literal, a `Scalar` becomes the single point `[value,value]` and a `List` or `Array` spans from its
+	/// first to its last element (`[first,last)`)
+	fn range_literal(&self) -> String {
+		let (lower, upper, lower_inclusive, upper_inclusive) = match self {
+			FilterValue::Scalar(value) => (Some(value), Some(value), true, true),
+			FilterValue::List(values) | FilterValue::Array(values) if values.is_empty() => return String::from("empty"),
+			FilterValue::List(values) | FilterValue::Array(values) if values.len() == 1 => (values.first(), values.first(), true, true),
+			FilterValue::List(values) | FilterValue::Array(values) => (values.first(), values.last(), true, false),
+			FilterValue::Range {
+				lower,
+				upper,
+				lower_inclusive,
+				upper_inclusive,
+			} => (lower.as_ref(), upper.as_ref(), *lower_inclusive, *upper_inclusive),
+		};
+		format!(
+			"{}{},{}{}",
+			if lower_inclusive { "[" } else { "(" },
+			lower.map(|value| quote(value, RANGE_RESERVED)).unwrap_or_default(),
+			upper.map(|value| quote(value, RANGE_RESERVED)).unwrap_or_default(),
+			if upper_inclusive { "]" } else { ")" },
+		)
+	}
+}
+
+/// A list for `in` e.g. `(1,2,"a,b")`
+fn list(values: &[String]) -> String {
+	format!(
+		"({})",
+		values.iter().map(|value| quote(value, LIST_RESERVED)).collect::<Vec<String>>().join(",")
+	)
+}
+
+/// A Postgres array literal e.g. `{rust,"c,d"}`
+fn array(values: &[String]) -> String {
+	format!(
+		"{{{}}}",
+		values.iter().map(|value| quote_array_element(value)).collect::<Vec<String>>().join(",")
+	)
+}
+
+/// Characters PostgREST treats as syntax inside lists and logical operators
+const LIST_RESERVED: &[char] = &[',', '.', ':', '(', ')', '"', '\\'];
+
+/// Characters Postgres treats as syntax inside a range literal
+const RANGE_RESERVED: &[char] = &[',', '(', ')', '[', ']', '"', '\\'];
+
+/// Wrap `value` in double quotes (escaping `"` and `\`) when it is empty or contains whitespace or a reserved character
+fn quote(value: &str, reserved: &[char]) -> String {
+	if !value.is_empty() && !value.chars().any(|c| c.is_whitespace() || reserved.contains(&c)) {
+		return value.to_string();
+	}
+	format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Quote an element of a Postgres array literal, `NULL` must be quoted to stay a string
+fn quote_array_element(value: &str) -> String {
+	if value.eq_ignore_ascii_case("null") {
+		return format!("\"{}\"", value);
+	}
+	quote(value, &['{', '}', ',', '"', '\\'])
+}
+
+impl From<&str> for FilterValue {
+	fn from(value: &str) -> Self {
+		FilterValue::Scalar(value.to_string())
+	}
+}
+
+impl From<String> for FilterValue {
+	fn from(value: String) -> Self {
+		FilterValue::Scalar(value)
+	}
+}
+
+impl From<&String> for FilterValue {
+	fn from(value: &String) -> Self {
+		FilterValue::Scalar(value.clone())
+	}
+}
+
+macro_rules! impl_from_scalar {
+	($($t:ty),*) => {
+		$(
+			impl From<$t> for FilterValue {
+				fn from(value: $t) -> Self {
+					FilterValue::Scalar(value.to_string())
+				}
+			}
+		)*
+	};
+}
+
+impl_from_scalar!(bool, i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize, f32, f64);
+
+impl<V: ToString> From<Vec<V>> for FilterValue {
+	fn from(values: Vec<V>) -> Self {
+		FilterValue::list(values)
+	}
+}
+
+impl<V: ToString> From<&[V]> for FilterValue {
+	fn from(values: &[V]) -> Self {
+		FilterValue::List(values.iter().map(|value| value.to_string()).collect())
+	}
+}
@@ -1,6 +1,1254 @@
 use postgrest_query::client::PostgrestClient;
+use serde::{Deserialize, Serialize};
+
+const URL: &str = "http://localhost:3000";
+
+#[derive(Debug, Deserialize, Serialize)]
+struct User {
+	id: i32,
+	name: String,
+}
+
+/// Percent-decoded query string, easier to compare against PostgREST's docs
+fn decoded_query(url: &url::Url) -> String {
+	url.query_pairs()
+		.map(|(key, value)| format!("{}={}", key, value))
+		.collect::<Vec<String>>()
+		.join("&")
+}
+
+/// Serve a single canned HTTP response on a random local port, returning the base URL and the raw request received
+///
+/// `head` is the status line and headers, each ending in `\r\n`, `Content-Length` is added automatically.
+fn serve_once(head: &'static str, body: &'static str) -> (String, std::sync::mpsc::Receiver<String>) {
+	use std::io::{BufRead, BufReader, Read, Write};
+
+	let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+	let url = format!("http://{}", listener.local_addr().unwrap());
+	let (sender, receiver) = std::sync::mpsc::channel();
+	std::thread::spawn(move || {
+		let (mut stream, _) = listener.accept().unwrap();
+		let mut reader = BufReader::new(stream.try_clone().unwrap());
+		let mut request = String::new();
+		let mut content_length = 0;
+		loop {
+			let mut line = String::new();
+			reader.read_line(&mut line).unwrap();
+			if let Some((name, value)) = line.split_once(':') {
+				if name.eq_ignore_ascii_case("content-length") {
+					content_length = value.trim().parse().unwrap();
+				}
+			}
+			request.push_str(&line);
+			if line == "\r\n" {
+				break;
+			}
+		}
+		let mut request_body = vec![0; content_length];
+		reader.read_exact(&mut request_body).unwrap();
+		request.push_str(&String::from_utf8(request_body).unwrap());
+		let response = format!("{}Content-Length: {}\r\n\r\n{}", head, body.len(), body);
+		stream.write_all(response.as_bytes()).unwrap();
+		sender.send(request).unwrap();
+	});
+	(url, receiver)
+}
 
 #[test]
 fn test_query() {
-    assert_eq!(1 + 1, 2);
+	assert_eq!(1 + 1, 2);
+}
+
+#[test]
+fn test_select() {
+	let query = PostgrestClient::new(URL.to_string(), None)
+		.unwrap()
+		.from("users")
+		.find_many::<User>()
+		.select("id, full_name:name, created_at::text, data->owner->>name, \"first name\"")
+		.select("id, name");
+	assert_eq!(query.url.query(), Some("select=id%2Cname"));
+
+	let query = PostgrestClient::new(URL.to_string(), None)
+		.unwrap()
+		.from("users")
+		.find_many::<User>()
+		.select("id, full_name:name, created_at::text, data->owner->>name, \"first name\"");
+	assert_eq!(
+		decoded_query(&query.url),
+		"select=id,full_name:name,created_at::text,data->owner->>name,\"first name\""
+	);
+
+	let query = PostgrestClient::new(URL.to_string(), None)
+		.unwrap()
+		.from("users")
+		.update(
+			User {
+				id: 1,
+				name: String::from("darren"),
+			},
+			None,
+		)
+		.eq("id", "1")
+		.select("id");
+	assert_eq!(query.url.query(), Some("id=eq.1&select=id"));
+	assert_eq!(query.prefer.to_string(), "count=exact");
+}
+
+#[test]
+fn test_embed() {
+	use postgrest_query::select::Embed;
+
+	let orders = Embed::new("orders")
+		.hint("customer_fk")
+		.inner()
+		.select("id, total")
+		.embed(Embed::new("products").alias("items").hint("order_items").select("name"));
+	let query = PostgrestClient::new(URL.to_string(), None)
+		.unwrap()
+		.from("customers")
+		.find_many::<User>()
+		.select("id,name")
+		.embed(orders)
+		.embed(Embed::new("addresses"));
+	assert_eq!(
+		decoded_query(&query.url),
+		"select=id,name,orders!customer_fk!inner(id,total,items:products!order_items(name)),addresses(*)"
+	);
+
+	let query = PostgrestClient::new(URL.to_string(), None)
+		.unwrap()
+		.from("customers")
+		.find_many::<User>()
+		.embed(Embed::new("orders"));
+	assert_eq!(query.url.query(), Some("select=*%2Corders%28*%29"));
+
+	// Embeds survive a `select` called after them
+	let query = PostgrestClient::new(URL.to_string(), None)
+		.unwrap()
+		.from("customers")
+		.find_many::<User>()
+		.embed(Embed::new("orders").select("id"))
+		.select("id, name")
+		.embed(Embed::new("addresses"))
+		.select("id");
+	assert_eq!(decoded_query(&query.url), "select=id,orders(id),addresses(*)");
+}
+
+#[test]
+fn test_embedded_modifiers() {
+	use postgrest_query::{
+		filter::{FilterType, Order},
+		select::Embed,
+	};
+
+	let query = PostgrestClient::new(URL.to_string(), None)
+		.unwrap()
+		.from("customers")
+		.find_many::<User>()
+		.embed(Embed::new("orders").embed(Embed::new("items")))
+		.embedded_filter("orders", "status", "paid", FilterType::Eq)
+		.embedded_order("orders", Order::desc("created_at"))
+		.embedded_order("orders", Order::asc("id"))
+		.embedded_limit("orders", 5)
+		.embedded_offset("orders.items", 10)
+		.embedded_not_null("orders");
+	assert_eq!(
+		decoded_query(&query.url),
+		"select=*,orders(*,items(*))&orders.status=eq.paid&orders.order=created_at.desc,id.asc&orders.limit=5&orders.items.offset=10&orders=not.is.null"
+	);
+}
+
+#[test]
+fn test_order() {
+	use postgrest_query::filter::Order;
+
+	let query = PostgrestClient::new(URL.to_string(), None)
+		.unwrap()
+		.from("posts")
+		.find_many::<User>()
+		.order(Order::desc("created_at").nulls_last())
+		.order(Order::asc("data->>age").nulls_first())
+		.order(Order::asc("name").embedded("author"));
+	assert_eq!(
+		decoded_query(&query.url),
+		"order=created_at.desc.nullslast,data->>age.asc.nullsfirst,author(name).asc"
+	);
+}
+
+#[test]
+fn test_pagination() {
+	use postgrest_query::handler::ContentRange;
+
+	let query = PostgrestClient::new(URL.to_string(), None)
+		.unwrap()
+		.from("users")
+		.find_many::<User>()
+		.limit(10)
+		.offset(20)
+		.limit(5)
+		.range(0, 24);
+	assert_eq!(decoded_query(&query.url), "offset=20&limit=5");
+	let headers = query.headers.unwrap();
+	assert_eq!(headers.get("Range").unwrap(), "0-24");
+	assert_eq!(headers.get("Range-Unit").unwrap(), "items");
+
+	assert_eq!(
+		"0-24/3573".parse(),
+		Ok(ContentRange {
+			range: Some((0, 24)),
+			total: Some(3573)
+		})
+	);
+	assert_eq!("*/0".parse(), Ok(ContentRange { range: None, total: Some(0) }));
+	assert_eq!(
+		"0-24/*".parse(),
+		Ok(ContentRange {
+			range: Some((0, 24)),
+			total: None
+		})
+	);
+	assert!("items 0-24".parse::<ContentRange>().is_err());
+	assert!("5-2/*".parse::<ContentRange>().is_err());
+}
+
+#[test]
+fn test_logical_conditions() {
+	use postgrest_query::{condition::Condition, filter::FilterType};
+
+	let query = PostgrestClient::new(URL.to_string(), None)
+		.unwrap()
+		.from("people")
+		.find_many::<User>()
+		.or(vec![
+			Condition::filter("age", "18", FilterType::Lt),
+			Condition::and(vec![
+				Condition::filter("age", "65", FilterType::Gte),
+				!Condition::filter("is_retired", "true", FilterType::Is),
+			]),
+		])
+		.not("name", "null", FilterType::Is)
+		.condition(!Condition::or(vec![
+			Condition::filter("a", "1", FilterType::Eq),
+			Condition::filter("b", "2", FilterType::Eq),
+		]))
+		.condition(!!Condition::filter("c", "3", FilterType::Gt))
+		.embedded_condition(
+			"orders",
+			Condition::or(vec![
+				Condition::filter("status", "paid", FilterType::Eq),
+				!Condition::and(vec![
+					Condition::filter("total", "100", FilterType::Gt),
+					Condition::filter("status", "refunded", FilterType::Eq),
+				]),
+			]),
+		);
+	assert_eq!(
+		decoded_query(&query.url),
+		"or=(age.lt.18,and(age.gte.65,is_retired.not.is.true))&name=not.is.null&not.or=(a.eq.1,b.eq.2)&c=gt.3&orders.or=(status.eq.paid,not.and(total.gt.100,status.eq.refunded))"
+	);
+}
+
+#[test]
+fn test_filter_value_encoding() {
+	use postgrest_query::{condition::Condition, filter::FilterType, value::FilterValue};
+
+	let query = PostgrestClient::new(URL.to_string(), None)
+		.unwrap()
+		.from("users")
+		.find_many::<User>()
+		.eq("name", "Smith, J. (Jr.)")
+		.gt("age", 18)
+		.is("deleted_at", "null")
+		.in_filter("name", vec!["O'Brien, Pat", "a\"b", "(x)", "plain"])
+		.in_filter("id", "1),(2")
+		.cs("tags", FilterValue::array(vec!["rust", "c,d", "NULL", "{}"]))
+		.ov("period", FilterValue::range("2017-01-01".."2017-03-31"))
+		.adj("ages", FilterValue::range(..=5))
+		.or(vec![
+			Condition::filter("name", "a,b),id.eq.(1", FilterType::Eq),
+			Condition::filter("id", vec![1, 2], FilterType::In),
+			Condition::filter("tags", FilterValue::array(vec!["x", "y"]), FilterType::Cs),
+		]);
+	assert_eq!(
+		decoded_query(&query.url),
+		[
+			"name=eq.Smith, J. (Jr.)",
+			"age=gt.18",
+			"deleted_at=is.null",
+			"name=in.(\"O'Brien, Pat\",\"a\\\"b\",\"(x)\",plain)",
+			"id=in.(\"1),(2\")",
+			"tags=cs.{rust,\"c,d\",\"NULL\",\"{}\"}",
+			"period=ov.[2017-01-01,2017-03-31)",
+			"ages=adj.(,5]",
+			"or=(name.eq.\"a,b),id.eq.(1\",id.in.(1,2),tags.cs.\"{x,y}\")",
+		]
+		.join("&")
+	);
+
+	// The operator picks the brackets, and a lone scalar can't turn into several elements
+	let query = PostgrestClient::new(URL.to_string(), None)
+		.unwrap()
+		.from("posts")
+		.find_many::<User>()
+		.cs("tags", vec!["a", "b"])
+		.cd("tags", "{x,y}")
+		.ov("tags", "a,b")
+		.in_filter("id", FilterValue::array(vec![1, 2]))
+		.sl("period", "{x,y}")
+		.nxr("period", vec![1, 10])
+		.or(vec![
+			Condition::filter("tags", vec!["a", "b"], FilterType::Cs),
+			Condition::filter("tags", "{x,y}", FilterType::Ov),
+		]);
+	assert_eq!(
+		decoded_query(&query.url),
+		[
+			"tags=cs.{a,b}",
+			"tags=cd.{\"{x,y}\"}",
+			"tags=ov.{\"a,b\"}",
+			"id=in.(1,2)",
+			"period=sl.[\"{x,y}\",\"{x,y}\"]",
+			"period=nxr.[1,10)",
+			"or=(tags.cs.\"{a,b}\",tags.ov.\"{\\\"{x,y}\\\"}\")",
+		]
+		.join("&")
+	);
+}
+
+#[test]
+fn test_quantified_filters() {
+	use postgrest_query::{
+		condition::Condition,
+		filter::{FilterType, Quantifier},
+	};
+
+	let query = PostgrestClient::new(URL.to_string(), None)
+		.unwrap()
+		.from("users")
+		.find_many::<User>()
+		.eq_any("name", &["a", "b,c"])
+		.like_all("name", &["O*", "*n"])
+		.gte_any("age", &[18, 21])
+		.imatch_all("email", &["^a", "com$"])
+		.or(vec![
+			Condition::quantified("id", &[1, 2], FilterType::Lt, Quantifier::All),
+			Condition::filter("id", 5, FilterType::Eq),
+		]);
+	assert_eq!(
+		decoded_query(&query.url),
+		"name=eq(any).{a,\"b,c\"}&name=like(all).{O*,*n}&age=gte(any).{18,21}&email=imatch(all).{^a,com$}&or=(id.lt(all).\"{1,2}\",id.eq.5)"
+	);
+}
+
+#[test]
+fn test_regex_distinct_and_fts() {
+	use postgrest_query::{condition::Condition, filter::FilterType};
+
+	let query = PostgrestClient::new(URL.to_string(), None)
+		.unwrap()
+		.from("posts")
+		.find_many::<User>()
+		.match_filter("title", "^Rust")
+		.imatch("title", "postgres$")
+		.is_distinct("status", "null")
+		.fts("body", "cat & dog", Some("english"))
+		.plfts("body", "fat cat", None)
+		.wfts("body", "\"fat cat\" or rat", Some("simple"))
+		.or(vec![Condition::filter(
+			"body",
+			"fat cat",
+			FilterType::Phfts(Some(String::from("english"))),
+		)]);
+	assert_eq!(
+		decoded_query(&query.url),
+		"title=match.^Rust&title=imatch.postgres$&status=isdistinct.null&body=fts(english).cat & dog&body=plfts.fat cat&body=wfts(simple).\"fat cat\" or rat&or=(body.phfts(english).\"fat cat\")"
+	);
+}
+
+#[test]
+fn test_match_columns() {
+	use std::collections::HashMap;
+
+	#[derive(Serialize)]
+	struct Search {
+		name: Option<String>,
+		age: Option<i32>,
+		active: bool,
+		tags: Vec<&'static str>,
+	}
+
+	let search = Search {
+		name: Some(String::from("Smith, J.")),
+		age: None,
+		active: true,
+		tags: vec!["a", "b,c"],
+	};
+	let query = PostgrestClient::new(URL.to_string(), None)
+		.unwrap()
+		.from("users")
+		.find_many::<User>()
+		.match_columns(&search);
+	assert_eq!(decoded_query(&query.url), "active=eq.true&name=eq.Smith, J.&tags=eq.{a,\"b,c\"}");
+
+	let mut search = HashMap::new();
+	search.insert("id", 7);
+	let query = PostgrestClient::new(URL.to_string(), None)
+		.unwrap()
+		.from("users")
+		.find_many::<User>()
+		.match_columns(&search);
+	assert_eq!(decoded_query(&query.url), "id=eq.7");
+}
+
+#[test]
+fn test_exec_response() {
+	let (url, request) = serve_once(
+		"HTTP/1.1 206 Partial Content\r\nContent-Range: 0-1/57\r\nPreference-Applied: count=exact\r\n",
+		r#"[{"id":1,"name":"a"},{"id":2,"name":"b"}]"#,
+	);
+	let res = PostgrestClient::new(url, None)
+		.unwrap()
+		.from("users")
+		.find_many::<User>()
+		.range(0, 1)
+		.exec_blocking_response()
+		.unwrap();
+	assert!(request.recv().unwrap().starts_with("GET /users HTTP/1.1\r\n"));
+	assert_eq!(res.data.len(), 2);
+	assert_eq!(res.status, 206);
+	assert_eq!(res.count, Some(57));
+	assert_eq!(res.range.unwrap().range, Some((0, 1)));
+	assert_eq!(res.preference_applied.as_deref(), Some("count=exact"));
+}
+
+#[test]
+fn test_create_counts() {
+	use postgrest_query::{builder::Return, handler::PostgrestError};
+
+	let users = vec![
+		User {
+			id: 1,
+			name: String::from("a"),
+		},
+		User {
+			id: 2,
+			name: String::from("b"),
+		},
+	];
+	let (url, request) = serve_once("HTTP/1.1 201 Created\r\nContent-Range: */1\r\n", "");
+	let count = PostgrestClient::new(url, None)
+		.unwrap()
+		.from("users")
+		.create_many(users, Some(false), None)
+		.prefer_return(Return::HeadersOnly)
+		.exec_blocking()
+		.unwrap();
+	assert_eq!(count, 1);
+	let request = request.recv().unwrap();
+	assert!(request.contains("prefer: return=headers-only,count=exact,missing=default\r\n"));
+
+	let (url, request) = serve_once("HTTP/1.1 201 Created\r\nContent-Range: */1\r\n", r#"[{"id":1,"name":"a"}]"#);
+	let users = PostgrestClient::new(url, None)
+		.unwrap()
+		.from("users")
+		.create(
+			User {
+				id: 1,
+				name: String::from("a"),
+			},
+			None,
+			None,
+		)
+		.returning::<User>()
+		.select("id,name")
+		.exec_blocking()
+		.unwrap();
+	assert_eq!(users.len(), 1);
+	let request = request.recv().unwrap();
+	assert!(request.starts_with("POST /users?select=id%2Cname HTTP/1.1\r\n"));
+	assert!(request.contains("prefer: return=representation,count=exact\r\n"));
+
+	// A missing, uncounted or malformed `Content-Range` is an error rather than a made-up or overflowing row count
+	for head in [
+		"HTTP/1.1 201 Created\r\n",
+		"HTTP/1.1 201 Created\r\nContent-Range: */*\r\n",
+		"HTTP/1.1 201 Created\r\nContent-Range: 5-2/*\r\n",
+		"HTTP/1.1 201 Created\r\nContent-Range: 0-18446744073709551615/*\r\n",
+	] {
+		let (url, _) = serve_once(head, "");
+		let result = PostgrestClient::new(url, None)
+			.unwrap()
+			.from("users")
+			.create(
+				User {
+					id: 1,
+					name: String::from("a"),
+				},
+				None,
+				None,
+			)
+			.exec_blocking();
+		assert!(matches!(result, Err(PostgrestError::InvalidContentRange(_))));
+	}
+
+	let (url, _) = serve_once("HTTP/1.1 204 No Content\r\nContent-Range: */3000000000\r\n", "");
+	let db = PostgrestClient::new(url, None).unwrap();
+	assert_eq!(db.from("events").delete::<User>(None).all_rows().exec_blocking().unwrap(), 3_000_000_000);
+}
+
+#[test]
+fn test_preferences_merge() {
+	use postgrest_query::{
+		builder::Count,
+		prefer::{Preferences, Tx},
+	};
+	use reqwest::header::HeaderMap;
+
+	let mut headers = HeaderMap::new();
+	headers.insert("Prefer", "tx=rollback, count=planned, timezone=UTC, x-custom=1".parse().unwrap());
+	let query = PostgrestClient::new(URL.to_string(), Some(headers))
+		.unwrap()
+		.from("users")
+		.update(
+			User {
+				id: 1,
+				name: String::from("a"),
+			},
+			Some(Count::Exact),
+		)
+		.eq("id", 1)
+		.prefer(Preferences {
+			max_affected: Some(1),
+			other: vec![String::from("x-custom=1")],
+			..Default::default()
+		});
+	assert_eq!(query.prefer.tx, Some(Tx::Rollback));
+	assert_eq!(query.prefer.to_string(), "count=exact,tx=rollback,timezone=UTC,max-affected=1,x-custom=1");
+	assert!(query.headers.unwrap().get("Prefer").is_none());
+
+	// Arguments left as `None` keep what the client's `Prefer` header chose, defaults only fill in the gaps
+	let mut headers = HeaderMap::new();
+	headers.insert("Prefer", "count=planned, resolution=ignore-duplicates".parse().unwrap());
+	let db = PostgrestClient::new(URL.to_string(), Some(headers)).unwrap();
+	let user = || User {
+		id: 1,
+		name: String::from("a"),
+	};
+	assert_eq!(db.from("users").update(user(), None).prefer.count, Some(Count::Planned));
+	assert_eq!(db.from("users").delete::<User>(None).prefer.count, Some(Count::Planned));
+	assert_eq!(
+		db.from("users").upsert(user(), None, None, None, None).prefer.to_string(),
+		"count=planned,resolution=ignore-duplicates"
+	);
+	assert_eq!(
+		db.from("users")
+			.upsert(user(), None, None, Some(Count::Exact), Some(false))
+			.prefer
+			.to_string(),
+		"count=exact,resolution=merge-duplicates"
+	);
+	let db = PostgrestClient::new(URL.to_string(), None).unwrap();
+	assert_eq!(
+		db.from("users").upsert(user(), None, None, None, None).prefer.to_string(),
+		"count=exact,resolution=merge-duplicates"
+	);
+
+	let (url, request) = serve_once("HTTP/1.1 201 Created\r\nContent-Range: */1\r\n", "");
+	let mut headers = HeaderMap::new();
+	headers.insert("Prefer", "count=planned".parse().unwrap());
+	let db = PostgrestClient::new(url, Some(headers)).unwrap();
+	assert_eq!(db.from("users").create(user(), None, None).exec_blocking().unwrap(), 1);
+	assert!(request.recv().unwrap().to_lowercase().contains("prefer: count=planned\r\n"));
+
+	assert_eq!(
+		Preferences::parse(&Preferences::parse("return=minimal,params=single-object,handling=strict").to_string()).to_string(),
+		"return=minimal,handling=strict,params=single-object"
+	);
+	assert_eq!(
+		Preferences::parse("return=minimal, return=representation").to_string(),
+		"return=representation"
+	);
+
+	// Every `Prefer` header is merged, one that isn't valid text fails the query
+	let mut headers = HeaderMap::new();
+	headers.append("Prefer", "count=planned,tx=rollback".parse().unwrap());
+	headers.append("Prefer", "count=estimated".parse().unwrap());
+	let query = PostgrestClient::new(URL.to_string(), Some(headers))
+		.unwrap()
+		.from("users")
+		.find_many::<User>();
+	assert_eq!(query.prefer.to_string(), "count=estimated,tx=rollback");
+
+	let mut headers = HeaderMap::new();
+	headers.insert("Prefer", reqwest::header::HeaderValue::from_bytes("timezone=Zürich".as_bytes()).unwrap());
+	let err = PostgrestClient::new(URL.to_string(), Some(headers))
+		.unwrap()
+		.from("users")
+		.find_many::<User>()
+		.exec_blocking()
+		.unwrap_err();
+	assert!(matches!(err, postgrest_query::handler::PostgrestError::InvalidHeader { .. }));
+}
+
+#[test]
+fn test_schema_profiles() {
+	let db = PostgrestClient::new(URL.to_string(), None).unwrap();
+
+	let query = db.schema("billing").from("invoices").find_many::<User>();
+	assert_eq!(query.schema.as_deref(), Some("billing"));
+
+	let query = db.schema("billing").from("events").schema("analytics").create(
+		User {
+			id: 1,
+			name: String::from("a"),
+		},
+		None,
+		None,
+	);
+	assert_eq!(query.schema.as_deref(), Some("analytics"));
+
+	assert!(db.from("users").find_many::<User>().schema.is_none());
+
+	// Reads select the schema with `Accept-Profile`, writes with `Content-Profile`
+	let (url, request) = serve_once("HTTP/1.1 200 OK\r\n", "[]");
+	let db = PostgrestClient::new(url, None).unwrap().schema("billing");
+	db.from("invoices").find_many::<User>().exec_blocking().unwrap();
+	let request = request.recv().unwrap().to_lowercase();
+	assert!(request.contains("accept-profile: billing\r\n"));
+	assert!(!request.contains("content-profile"));
+
+	let (url, request) = serve_once("HTTP/1.1 204 No Content\r\n", "");
+	let db = PostgrestClient::new(url, None).unwrap().schema("billing");
+	let deleted = db
+		.from("invoices")
+		.delete::<User>(None)
+		.eq("id", 1)
+		.prefer_return(postgrest_query::builder::Return::Minimal);
+	let _ = deleted.exec_blocking();
+	let request = request.recv().unwrap().to_lowercase();
+	assert!(request.contains("content-profile: billing\r\n"));
+	assert!(!request.contains("accept-profile"));
+}
+
+#[test]
+fn test_client_is_shareable() {
+	fn assert_send_sync<T: Send + Sync + Clone + 'static>() {}
+	assert_send_sync::<PostgrestClient>();
+
+	let db = std::sync::Arc::new(PostgrestClient::new(URL.to_string(), None).unwrap());
+	let handles: Vec<_> = (0..4)
+		.map(|id| {
+			let db = db.clone();
+			std::thread::spawn(move || db.from("users").find_many::<User>().eq("id", id).url.to_string())
+		})
+		.collect();
+	for (id, handle) in handles.into_iter().enumerate() {
+		assert_eq!(handle.join().unwrap(), format!("{}/users?id=eq.{}", URL, id));
+	}
+
+	// Queries can be spawned onto a multi-threaded runtime
+	fn assert_send<F: Send>(_: &F) {}
+	assert_send(&db.from("users").find_many::<User>().exec());
+	assert_send(&db.call::<serde_json::Value>("add_them", false, None, std::collections::HashMap::new()));
+
+	let cloned = db.as_ref().clone();
+	assert_eq!(cloned.from("posts").url.as_str(), db.from("posts").url.as_str());
+}
+
+#[test]
+fn test_connection_pool_is_reused() {
+	use postgrest_query::http::PoolConfig;
+	use std::{
+		io::{BufRead, BufReader, Write},
+		sync::{
+			atomic::{AtomicUsize, Ordering},
+			Arc,
+		},
+	};
+
+	// Answers every GET on a connection with `[]` and keeps the connection open
+	let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+	let url = format!("http://{}", listener.local_addr().unwrap());
+	let connections = Arc::new(AtomicUsize::new(0));
+	let accepted = connections.clone();
+	std::thread::spawn(move || {
+		for stream in listener.incoming() {
+			accepted.fetch_add(1, Ordering::SeqCst);
+			let mut stream = stream.unwrap();
+			std::thread::spawn(move || {
+				let mut reader = BufReader::new(stream.try_clone().unwrap());
+				loop {
+					let mut line = String::new();
+					if reader.read_line(&mut line).unwrap_or(0) == 0 {
+						return;
+					}
+					if line == "\r\n" {
+						stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n[]").unwrap();
+					}
+				}
+			});
+		}
+	});
+
+	let db = PostgrestClient::new(url, None).unwrap().pool(PoolConfig {
+		max_idle_per_host: Some(4),
+		idle_timeout: Some(std::time::Duration::from_secs(30)),
+	});
+	for _ in 0..3 {
+		let users = db.from("users").find_many::<User>().exec_blocking().unwrap();
+		assert!(users.is_empty());
+	}
+	let users = db.clone().from("users").find_many::<User>().exec_blocking().unwrap();
+	assert!(users.is_empty());
+	assert_eq!(connections.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_custom_transport() {
+	use postgrest_query::{
+		handler::PostgrestError,
+		transport::{Transport, TransportFuture, TransportRequest, TransportResponse},
+	};
+	use reqwest::{header::HeaderMap, StatusCode};
+	use std::sync::{Arc, Mutex};
+
+	/// Records every request and answers with the same canned body
+	#[derive(Debug, Default)]
+	struct Recorder {
+		requests: Arc<Mutex<Vec<TransportRequest>>>,
+	}
+
+	impl Transport for Recorder {
+		fn send(&self, request: TransportRequest) -> TransportFuture<'_> {
+			Box::pin(async move { self.send_blocking(request) })
+		}
+
+		fn send_blocking(&self, request: TransportRequest) -> Result<TransportResponse, PostgrestError> {
+			self.requests.lock().unwrap().push(request);
+			let mut headers = HeaderMap::new();
+			headers.insert("Content-Range", "0-0/1".parse().unwrap());
+			Ok(TransportResponse {
+				status: StatusCode::CREATED,
+				headers,
+				body: br#"[{"id":1,"name":"Ada"}]"#.to_vec(),
+			})
+		}
+	}
+
+	let recorder = Recorder::default();
+	let requests = recorder.requests.clone();
+	let db = PostgrestClient::new(URL.to_string(), None).unwrap().transport(recorder);
+
+	let users = db.from("users").find_many::<User>().eq("id", 1).exec_blocking().unwrap();
+	assert_eq!(users[0].name, "Ada");
+
+	let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+	let inserted = runtime
+		.block_on(
+			db.from("users")
+				.create(
+					User {
+						id: 1,
+						name: "Ada".to_string(),
+					},
+					None,
+					None,
+				)
+				.exec(),
+		)
+		.unwrap();
+	assert_eq!(inserted, 1);
+
+	let requests = requests.lock().unwrap();
+	assert_eq!(requests.len(), 2);
+	assert_eq!(requests[0].method, reqwest::Method::GET);
+	assert_eq!(decoded_query(&requests[0].url), "id=eq.1");
+	assert!(requests[0].body.is_none());
+	assert_eq!(requests[1].method, reqwest::Method::POST);
+	assert_eq!(requests[1].headers["Content-Type"], "application/json");
+	assert_eq!(requests[1].headers["Prefer"], "count=exact");
+	assert_eq!(requests[1].body.as_deref(), Some(br#"{"id":1,"name":"Ada"}"#.as_slice()));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_unix_socket_transport() {
+	use postgrest_query::unix::UnixSocketTransport;
+	use std::io::{BufRead, BufReader, Read, Write};
+
+	// Answers two requests, the first with a chunked body and the second with `Content-Length`
+	let path = std::env::temp_dir().join(format!("postgrest-query-{}.sock", std::process::id()));
+	let _ = std::fs::remove_file(&path);
+	let listener = std::os::unix::net::UnixListener::bind(&path).unwrap();
+	let (sender, receiver) = std::sync::mpsc::channel();
+	std::thread::spawn(move || {
+		let responses = [
+			"HTTP/1.1 200 OK\r\nContent-Range: 0-0/*\r\nTransfer-Encoding: chunked\r\n\r\n6\r\n[{\"id\"\r\nf\r\n:1,\"name\":\"Ada\"\r\n2\r\n}]\r\n0\r\n\r\n",
+			"HTTP/1.1 201 Created\r\nContent-Range: */1\r\nContent-Length: 0\r\n\r\n",
+		];
+		for response in responses {
+			let (mut stream, _) = listener.accept().unwrap();
+			let mut reader = BufReader::new(stream.try_clone().unwrap());
+			let mut request = String::new();
+			let mut content_length = 0;
+			loop {
+				let mut line = String::new();
+				reader.read_line(&mut line).unwrap();
+				if let Some(("Content-Length", value)) = line.split_once(':') {
+					content_length = value.trim().parse().unwrap();
+				}
+				request.push_str(&line);
+				if line == "\r\n" {
+					break;
+				}
+			}
+			let mut request_body = vec![0; content_length];
+			reader.read_exact(&mut request_body).unwrap();
+			request.push_str(&String::from_utf8(request_body).unwrap());
+			stream.write_all(response.as_bytes()).unwrap();
+			sender.send(request).unwrap();
+		}
+	});
+
+	let db = PostgrestClient::new("http://localhost".to_string(), None)
+		.unwrap()
+		.transport(UnixSocketTransport::new(&path));
+
+	let users = db.from("users").find_many::<User>().eq("id", 1).exec_blocking().unwrap();
+	assert_eq!(users[0].name, "Ada");
+	let request = receiver.recv().unwrap();
+	assert!(request.starts_with("GET /users?id=eq.1 HTTP/1.1\r\n"));
+	assert!(request.contains("Host: localhost\r\n"));
+
+	let runtime = tokio::runtime::Builder::new_current_thread().enable_io().build().unwrap();
+	let inserted = runtime
+		.block_on(
+			db.from("users")
+				.create(
+					User {
+						id: 2,
+						name: "Grace".to_string(),
+					},
+					None,
+					None,
+				)
+				.exec(),
+		)
+		.unwrap();
+	assert_eq!(inserted, 1);
+	let request = receiver.recv().unwrap();
+	assert!(request.starts_with("POST /users HTTP/1.1\r\n"));
+	assert!(request.ends_with("\r\n\r\n{\"id\":2,\"name\":\"Grace\"}"));
+
+	std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_error_taxonomy() {
+	use postgrest_query::handler::PostgrestError;
+
+	let error = |status: &'static str, body: &'static str| {
+		let head = Box::leak(format!("HTTP/1.1 {}\r\nContent-Type: application/json\r\n", status).into_boxed_str());
+		let (url, _) = serve_once(head, body);
+		PostgrestClient::new(url, None)
+			.unwrap()
+			.from("users")
+			.find_many::<User>()
+			.exec_blocking()
+			.unwrap_err()
+	};
+
+	let err = error(
+		"409 Conflict",
+		r#"{"code":"23505","details":"Key (email)=(ada@example.com) already exists.","hint":null,"message":"duplicate key value violates unique constraint \"users_email_key\""}"#,
+	);
+	assert!(matches!(err, PostgrestError::UniqueViolation(_)));
+	assert_eq!(err.constraint().as_deref(), Some("users_email_key"));
+	assert_eq!(err.code(), Some("23505"));
+	assert_eq!(err.status(), Some(reqwest::StatusCode::CONFLICT));
+	assert_eq!(
+		err.to_string(),
+		"duplicate key value violates unique constraint \"users_email_key\" (23505)"
+	);
+
+	let err = error(
+		"409 Conflict",
+		r#"{"code":"23503","details":null,"hint":null,"message":"insert or update on table \"orders\" violates foreign key constraint \"orders_user_id_fkey\""}"#,
+	);
+	assert!(matches!(err, PostgrestError::ForeignKeyViolation(_)));
+	assert_eq!(err.constraint().as_deref(), Some("orders_user_id_fkey"));
+
+	let err = error(
+		"400 Bad Request",
+		r#"{"code":"23502","details":null,"hint":null,"message":"null value in column \"name\" of relation \"users\" violates not-null constraint"}"#,
+	);
+	assert!(matches!(err, PostgrestError::NotNullViolation(_)));
+	assert_eq!(err.column().as_deref(), Some("name"));
+
+	let err = error(
+		"403 Forbidden",
+		r#"{"code":"42501","details":null,"hint":null,"message":"new row violates row-level security policy for table \"users\""}"#,
+	);
+	assert!(matches!(err, PostgrestError::PermissionDenied(_)));
+
+	let err = error(
+		"401 Unauthorized",
+		r#"{"code":"PGRST301","details":null,"hint":null,"message":"JWT expired"}"#,
+	);
+	assert!(matches!(err, PostgrestError::JwtExpired(_)));
+
+	let err = error(
+		"404 Not Found",
+		r#"{"code":"PGRST205","details":null,"hint":"Perhaps you meant the table 'public.user'","message":"Could not find the table 'public.users' in the schema cache"}"#,
+	);
+	assert!(matches!(err, PostgrestError::NotFound(_)));
+
+	let err = error(
+		"300 Multiple Choices",
+		r#"{"code":"PGRST201","details":[],"hint":"Try changing 'orders' to one of the following","message":"Could not embed because more than one relationship was found for 'users' and 'orders'"}"#,
+	);
+	assert!(matches!(err, PostgrestError::AmbiguousEmbed(_)));
+
+	let err = error(
+		"409 Conflict",
+		r#"{"code":"23P01","details":"Key (room, during)=(1, [10:00,11:00)) conflicts with existing key.","hint":null,"message":"conflicting key value violates exclusion constraint \"bookings_no_overlap\""}"#,
+	);
+	assert!(matches!(err, PostgrestError::IntegrityViolation(_)));
+	assert_eq!(err.code(), Some("23P01"));
+
+	let err = error(
+		"500 Internal Server Error",
+		r#"{"code":"PGRST300","details":null,"hint":null,"message":"Server lacks JWT secret"}"#,
+	);
+	assert!(matches!(err, PostgrestError::PostgrestErrorResponse(_)));
+
+	let err = error(
+		"400 Bad Request",
+		r#"{"code":"22P02","details":null,"hint":null,"message":"invalid input syntax for type integer"}"#,
+	);
+	assert!(matches!(err, PostgrestError::PostgrestErrorResponse(_)));
+	let _: &dyn std::error::Error = &err;
+
+	// JSON without a `code` comes from something in front of PostgREST, its status is kept
+	let err = error("503 Service Unavailable", r#"{"message":"Service Unavailable"}"#);
+	assert!(matches!(err, PostgrestError::NonJsonErrorBody { .. }));
+	assert_eq!(err.status(), Some(reqwest::StatusCode::SERVICE_UNAVAILABLE));
+}
+
+#[test]
+fn test_errors_instead_of_panics() {
+	use postgrest_query::handler::PostgrestError;
+	use reqwest::StatusCode;
+
+	assert!(matches!(
+		PostgrestClient::new("not a url".to_string(), None),
+		Err(PostgrestError::InvalidUrl(_))
+	));
+	assert!(matches!(
+		PostgrestClient::new("mailto:db@example.com".to_string(), None),
+		Err(PostgrestError::InvalidUrl(_))
+	));
+
+	let db = PostgrestClient::new(format!("{}/api/", URL), None).unwrap();
+	assert_eq!(db.from("users").url.as_str(), "http://localhost:3000/api/users");
+
+	let err = db.schema("bad\nschema").from("users").find_many::<User>().exec_blocking().unwrap_err();
+	assert!(matches!(err, PostgrestError::InvalidHeader { ref name, .. } if name == "Accept-Profile"));
+
+	let err = db
+		.from("users")
+		.find_many::<User>()
+		.match_columns(&vec![1, 2])
+		.exec_blocking()
+		.unwrap_err();
+	assert!(matches!(err, PostgrestError::ArgumentEncoding(_)));
+
+	let (url, _) = serve_once("HTTP/1.1 502 Bad Gateway\r\nContent-Type: text/html\r\n", "<html>Bad Gateway</html>");
+	let err = PostgrestClient::new(url, None)
+		.unwrap()
+		.from("users")
+		.find_many::<User>()
+		.exec_blocking()
+		.unwrap_err();
+	match err {
+		PostgrestError::NonJsonErrorBody { status, body } => {
+			assert_eq!(status, StatusCode::BAD_GATEWAY);
+			assert_eq!(body, "<html>Bad Gateway</html>");
+		}
+		err => panic!("unexpected error {:?}", err),
+	}
+
+	let (url, _) = serve_once("HTTP/1.1 502 Bad Gateway\r\n", "");
+	let err = PostgrestClient::new(url, None)
+		.unwrap()
+		.from("users")
+		.find_many::<User>()
+		.exec_blocking()
+		.unwrap_err();
+	assert!(matches!(
+		err,
+		PostgrestError::NonJsonErrorBody {
+			status: StatusCode::BAD_GATEWAY,
+			..
+		}
+	));
+
+	let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+	let args = std::collections::HashMap::from([("filter", serde_json::json!({ "a": 1 }))]);
+	let err = runtime.block_on(db.call::<serde_json::Value>("search", true, None, args)).unwrap_err();
+	assert!(matches!(err, PostgrestError::ArgumentEncoding(_)));
+
+	let args = std::collections::HashMap::from([("id", serde_json::json!("not a number"))]);
+	let err = runtime.block_on(db.call::<User>("get_user", false, None, args)).unwrap_err();
+	assert!(matches!(err, PostgrestError::ArgumentEncoding(_)));
+}
+
+#[test]
+fn test_singular_queries() {
+	use postgrest_query::{filter::FilterType, handler::PostgrestError};
+
+	let (url, request) = serve_once("HTTP/1.1 200 OK\r\n", r#"{"id":1,"name":"Ada"}"#);
+	let db = PostgrestClient::new(url, None).unwrap();
+	let user = db.from("users").find_unique::<User, _>("id", FilterType::Eq, 1).exec_blocking().unwrap();
+	assert_eq!(user.name, "Ada");
+	let request = request.recv().unwrap().to_lowercase();
+	assert!(request.starts_with("get /users?id=eq.1 "));
+	assert!(request.contains("accept: application/vnd.pgrst.object+json\r\n"));
+
+	let (url, _) = serve_once(
+		"HTTP/1.1 406 Not Acceptable\r\n",
+		r#"{"code":"PGRST116","details":"The result contains 0 rows","hint":null,"message":"JSON object requested, multiple (or no) rows returned"}"#,
+	);
+	let err = PostgrestClient::new(url, None)
+		.unwrap()
+		.from("users")
+		.find_unique::<User, _>("id", FilterType::Eq, 2)
+		.exec_blocking()
+		.unwrap_err();
+	assert!(matches!(err, PostgrestError::RowNotFound(_)));
+
+	let (url, _) = serve_once(
+		"HTTP/1.1 406 Not Acceptable\r\n",
+		r#"{"code":"PGRST116","details":"Results contain 2 rows, application/vnd.pgrst.object+json requires 1 row","hint":null,"message":"JSON object requested, multiple (or no) rows returned"}"#,
+	);
+	let err = PostgrestClient::new(url, None)
+		.unwrap()
+		.from("users")
+		.find_unique::<User, _>("name", FilterType::Eq, "Ada")
+		.exec_blocking()
+		.unwrap_err();
+	assert!(matches!(err, PostgrestError::MultipleRows(_)));
+
+	let (url, request) = serve_once("HTTP/1.1 200 OK\r\n", r#"[{"id":3,"name":"Grace"}]"#);
+	let db = PostgrestClient::new(url, None).unwrap();
+	let user = db.from("users").find_first::<User>().gt("id", 2).exec_blocking().unwrap();
+	assert_eq!(user.unwrap().name, "Grace");
+	assert!(request.recv().unwrap().starts_with("GET /users?limit=1&id=gt.2 "));
+
+	let (url, _) = serve_once("HTTP/1.1 200 OK\r\n", "[]");
+	let db = PostgrestClient::new(url, None).unwrap();
+	assert!(db.from("users").find_first::<User>().exec_blocking().unwrap().is_none());
+}
+
+#[test]
+fn test_count_and_exists() {
+	use postgrest_query::{builder::Count, handler::PostgrestError};
+
+	let (url, request) = serve_once("HTTP/1.1 200 OK\r\nContent-Range: 0-24/3573\r\n", "");
+	let db = PostgrestClient::new(url, None).unwrap();
+	let count = db.from("users").find_many::<User>().gte("id", 18).count_blocking(Count::Exact).unwrap();
+	assert_eq!(count, 3573);
+	let request = request.recv().unwrap();
+	assert!(request.starts_with("HEAD /users?id=gte.18 "));
+	assert!(request.to_lowercase().contains("prefer: count=exact\r\n"));
+
+	let (url, request) = serve_once("HTTP/1.1 200 OK\r\nContent-Range: 0-0/*\r\n", "");
+	let db = PostgrestClient::new(url, None).unwrap();
+	let runtime = tokio::runtime::Builder::new_current_thread().enable_io().build().unwrap();
+	assert!(runtime.block_on(db.from("users").find_many::<User>().eq("name", "Ada").exists()).unwrap());
+	let request = request.recv().unwrap();
+	assert!(request.starts_with("HEAD /users?name=eq.Ada&limit=1 "));
+	assert!(!request.to_lowercase().contains("prefer"));
+
+	let (url, _) = serve_once("HTTP/1.1 200 OK\r\nContent-Range: */*\r\n", "");
+	let db = PostgrestClient::new(url, None).unwrap();
+	assert!(!db.from("users").find_many::<User>().eq("name", "Nobody").exists_blocking().unwrap());
+
+	// Without a total there is nothing to count, the range is only the page that was returned
+	for head in ["HTTP/1.1 200 OK\r\n", "HTTP/1.1 200 OK\r\nContent-Range: 0-24/*\r\n"] {
+		let (url, _) = serve_once(head, "");
+		let db = PostgrestClient::new(url, None).unwrap();
+		let err = db.from("users").find_many::<User>().count_blocking(Count::Exact).unwrap_err();
+		assert!(matches!(err, PostgrestError::InvalidContentRange(_)));
+	}
+}
+
+#[test]
+fn test_mutations_return_rows() {
+	use postgrest_query::handler::PostgrestError;
+
+	let ada = || User {
+		id: 1,
+		name: String::from("Ada"),
+	};
+
+	let (url, request) = serve_once(
+		"HTTP/1.1 200 OK\r\nContent-Range: 0-1/2\r\n",
+		r#"[{"id":1,"name":"Ada"},{"id":2,"name":"Ada"}]"#,
+	);
+	let db = PostgrestClient::new(url, None).unwrap();
+	let users = db
+		.from("users")
+		.update(ada(), None)
+		.lt("id", 3)
+		.returning::<User>()
+		.select("id,name")
+		.exec_blocking()
+		.unwrap();
+	assert_eq!(users.len(), 2);
+	let request = request.recv().unwrap();
+	assert!(request.starts_with("PATCH /users?id=lt.3&select=id%2Cname "));
+	assert!(request.to_lowercase().contains("prefer: return=representation,count=exact\r\n"));
+
+	let (url, request) = serve_once("HTTP/1.1 204 No Content\r\nContent-Range: */3\r\n", "");
+	let db = PostgrestClient::new(url, None).unwrap();
+	assert_eq!(db.from("users").delete::<User>(None).gt("id", 10).exec_blocking().unwrap(), 3);
+	assert!(request.recv().unwrap().starts_with("DELETE /users?id=gt.10 "));
+
+	let (url, request) = serve_once("HTTP/1.1 201 Created\r\nContent-Range: */1\r\n", "");
+	let db = PostgrestClient::new(url, None).unwrap();
+	assert_eq!(
+		db.from("users")
+			.upsert(ada(), Some("id".to_string()), None, None, None)
+			.exec_blocking()
+			.unwrap(),
+		1
+	);
+	let request = request.recv().unwrap();
+	assert!(request.starts_with("POST /users?on_conflict=id "));
+	assert!(request.to_lowercase().contains("prefer: count=exact,resolution=merge-duplicates\r\n"));
+
+	let (url, request) = serve_once("HTTP/1.1 200 OK\r\n", r#"{"id":1,"name":"Ada"}"#);
+	let db = PostgrestClient::new(url, None).unwrap();
+	let user = db.from("users").update_one(ada()).eq("id", 1).exec_blocking().unwrap();
+	assert_eq!(user.id, 1);
+	let request = request.recv().unwrap().to_lowercase();
+	assert!(request.contains("accept: application/vnd.pgrst.object+json\r\n"));
+	assert!(request.contains("prefer: return=representation\r\n"));
+
+	let (url, _) = serve_once(
+		"HTTP/1.1 406 Not Acceptable\r\n",
+		r#"{"code":"PGRST116","details":"Results contain 4 rows, application/vnd.pgrst.object+json requires 1 row","hint":null,"message":"JSON object requested, multiple (or no) rows returned"}"#,
+	);
+	let db = PostgrestClient::new(url, None).unwrap();
+	let err = db.from("users").delete_one::<User>().eq("name", "Ada").exec_blocking().unwrap_err();
+	assert!(matches!(err, PostgrestError::MultipleRows(_)));
+}
+
+#[test]
+fn test_mutation_guardrails() {
+	use postgrest_query::handler::PostgrestError;
+
+	let db = PostgrestClient::new(URL.to_string(), None).unwrap();
+	let patch = || User {
+		id: 1,
+		name: String::from("Ada"),
+	};
+
+	// Unfiltered updates and deletes don't compile (see `postgrest_query::state`), a filter that turns out to be empty
+	// is still refused before anything is sent
+	let nothing = serde_json::json!({ "id": null });
+	let err = db.from("users").delete::<User>(None).match_columns(&nothing).exec_blocking().unwrap_err();
+	assert!(matches!(err, PostgrestError::UnfilteredMutation));
+	let err = db
+		.from("users")
+		.update_one(patch())
+		.match_columns(&nothing)
+		.select("id")
+		.exec_blocking()
+		.unwrap_err();
+	assert!(matches!(err, PostgrestError::UnfilteredMutation));
+
+	let (url, request) = serve_once("HTTP/1.1 204 No Content\r\nContent-Range: */12\r\n", "");
+	let db = PostgrestClient::new(url, None).unwrap();
+	assert_eq!(db.from("sessions").delete::<User>(None).all_rows().exec_blocking().unwrap(), 12);
+	assert!(request.recv().unwrap().starts_with("DELETE /sessions "));
+
+	let (url, request) = serve_once("HTTP/1.1 204 No Content\r\nContent-Range: */2\r\n", "");
+	let db = PostgrestClient::new(url, None).unwrap();
+	let deleted = db
+		.from("users")
+		.delete_many::<User, _>("id", vec![1, 2], None)
+		.max_affected(2)
+		.exec_blocking()
+		.unwrap();
+	assert_eq!(deleted, 2);
+	let request = request.recv().unwrap();
+	assert!(request.starts_with("DELETE /users?id=in.%281%2C2%29 "));
+	assert!(request.to_lowercase().contains("prefer: count=exact,handling=strict,max-affected=2\r\n"));
+
+	let (url, _) = serve_once(
+		"HTTP/1.1 400 Bad Request\r\n",
+		r#"{"code":"PGRST124","details":"The query affects 40 rows","hint":null,"message":"Query result exceeds max-affected preference constraint"}"#,
+	);
+	let db = PostgrestClient::new(url, None).unwrap();
+	let err = db
+		.from("users")
+		.update(patch(), None)
+		.eq("team_id", 7)
+		.max_affected(5)
+		.exec_blocking()
+		.unwrap_err();
+	assert!(matches!(err, PostgrestError::MaxAffectedExceeded(_)));
+}
+
+#[test]
+fn test_typestate_transitions() {
+	use postgrest_query::{
+		builder::Return,
+		filter::FilterType,
+		handler::PostgrestError,
+		state::{Filtered, SingleFiltered},
+	};
+
+	let db = PostgrestClient::new(URL.to_string(), None).unwrap();
+	let user = || User {
+		id: 1,
+		name: String::from("Ada"),
+	};
+
+	// `find_unique` takes further filters and a select like any other query
+	let query = db
+		.from("users")
+		.find_unique::<User, _>("id", FilterType::Eq, 1)
+		.eq("active", true)
+		.select("id,name");
+	assert_eq!(decoded_query(&query.url), "id=eq.1&active=eq.true&select=id,name");
+
+	// The first filter (or `all_rows`) makes an update executable
+	let query: postgrest_query::filter::PostgrestFilter<u64, User, Filtered> = db.from("users").update(user(), None).eq("id", 1);
+	assert_eq!(decoded_query(&query.url), "id=eq.1");
+	let query: postgrest_query::filter::PostgrestFilter<u64, User, Filtered> = db.from("users").delete::<User>(None).all_rows();
+	assert!(query.all_rows);
+	let query: postgrest_query::filter::PostgrestFilter<User, User, SingleFiltered> = db.from("users").update_one(user()).eq("id", 1);
+	assert_eq!(query.prefer.to_string(), "return=representation");
+
+	// Rows asked for with `returning` can't be turned off again
+	let err = db
+		.from("users")
+		.update(user(), None)
+		.eq("id", 1)
+		.returning::<User>()
+		.prefer_return(Return::Minimal)
+		.exec_blocking()
+		.unwrap_err();
+	assert!(matches!(err, PostgrestError::ArgumentEncoding(_)));
+
+	let query = db
+		.from("users")
+		.upsert(user(), None, None, None, Some(true))
+		.on_conflict("email, team_id");
+	assert_eq!(decoded_query(&query.url), "on_conflict=email,team_id");
+	assert_eq!(query.prefer.to_string(), "count=exact,resolution=ignore-duplicates");
 }